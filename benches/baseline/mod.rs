@@ -0,0 +1,692 @@
+// The lexer as it was before lexemes borrowed from the input, kept unchanged as the
+// benchmark baseline. Every token owns a `String` lexeme.
+#![allow(dead_code, clippy::upper_case_acronyms)]
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenType {
+    Def,
+    Extern,
+    If,
+    Then,
+    Else,
+    For,
+    In,
+    While,
+    Var,
+    Let,
+    Return,
+    True,
+    False,
+    Delimiter,
+    LParenthesis,
+    RParenthesis,
+    LBracket,
+    RBracket,
+    Comma,
+    Comment,
+    DocComment,
+    Ident,
+    String,
+    Numeric,
+    Operator,
+    Arrow,
+    EOF,
+}
+
+// Reserved words, shared by everything that needs to know what a keyword is.
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("def", TokenType::Def),
+    ("extern", TokenType::Extern),
+    ("if", TokenType::If),
+    ("then", TokenType::Then),
+    ("else", TokenType::Else),
+    ("for", TokenType::For),
+    ("in", TokenType::In),
+    ("while", TokenType::While),
+    ("var", TokenType::Var),
+    ("let", TokenType::Let),
+    ("return", TokenType::Return),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+];
+
+pub fn keyword(ident: &str) -> Option<TokenType> {
+    KEYWORDS
+        .iter()
+        .find(|(name, _)| *name == ident)
+        .map(|(_, token_t)| token_t.clone())
+}
+
+// Every operator the lexer knows, longest first so `op` can match greedily.
+pub const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "->", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "+", "-", "*", "/", "%", "!", "<", ">", "=", "&", "|", "^",
+];
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Self {
+        Span {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Token {
+    pub token_t: TokenType,
+    pub lexeme: String,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(token_t: TokenType, lexeme: String, span: Span) -> Self {
+        Token {
+            token_t,
+            lexeme,
+            span,
+        }
+    }
+}
+
+impl Token {
+    // Typed value of a `Numeric` token.
+    pub fn number(&self) -> Option<Number> {
+        match self.token_t {
+            TokenType::Numeric => Number::parse(&self.lexeme, self.span).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Debug for Token {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", &self.to_string())
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<| type: {:?} + lexeme: {:?} + at: {} |>",
+            self.token_t, self.lexeme, self.span
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    // Parse a numeric literal as written in the source, Ex: 0xFF, 0b1010, 0o17, 1_000, 1.5e-9
+    pub fn parse(lexeme: &str, span: Span) -> Result<Number, LexError> {
+        let malformed = || LexError::MalformedNumber(lexeme.to_owned(), span);
+        let overflow = || LexError::NumberOverflow(lexeme.to_owned(), span);
+
+        let radix = match lexeme.get(..2) {
+            Some("0x") | Some("0X") => 16,
+            Some("0o") | Some("0O") => 8,
+            Some("0b") | Some("0B") => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            let digits = lexeme[2..].replace('_', "");
+            if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+                return Err(malformed());
+            }
+            return i64::from_str_radix(&digits, radix)
+                .map(Number::Int)
+                .map_err(|_| overflow());
+        }
+
+        // Decimal: digits [. digits] [e [+-] digits]
+        let mut chars = lexeme.chars().peekable();
+        let digits = |chars: &mut Peekable<Chars>| {
+            let mut found = false;
+            while let Some(c) = chars.peek() {
+                match c {
+                    '0'..='9' => found = true,
+                    '_' => {}
+                    _ => break,
+                }
+                chars.next();
+            }
+            found
+        };
+        let mut is_float = false;
+        if !digits(&mut chars) {
+            return Err(malformed());
+        }
+        if chars.peek() == Some(&'.') {
+            is_float = true;
+            chars.next();
+            digits(&mut chars);
+        }
+        if let Some('e') | Some('E') = chars.peek() {
+            is_float = true;
+            chars.next();
+            if let Some('+') | Some('-') = chars.peek() {
+                chars.next();
+            }
+            if !digits(&mut chars) {
+                return Err(malformed());
+            }
+        }
+        if chars.next().is_some() {
+            return Err(malformed());
+        }
+
+        let text = lexeme.replace('_', "");
+        if is_float {
+            match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(Number::Float(value)),
+                Ok(_) => Err(overflow()),
+                Err(_) => Err(malformed()),
+            }
+        } else {
+            text.parse::<i64>().map(Number::Int).map_err(|_| overflow())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexError {
+    UnexpectedChar(char, Span),
+    UnterminatedString(Span),
+    MalformedNumber(String, Span),
+    NumberOverflow(String, Span),
+    InvalidEscape(String, Span),
+    UnterminatedComment(Span),
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedChar(_, span) => *span,
+            LexError::UnterminatedString(span) => *span,
+            LexError::MalformedNumber(_, span) => *span,
+            LexError::NumberOverflow(_, span) => *span,
+            LexError::InvalidEscape(_, span) => *span,
+            LexError::UnterminatedComment(span) => *span,
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LexError::UnexpectedChar(c, span) => {
+                write!(f, "{}: unexpected character {:?}", span, c)
+            }
+            LexError::UnterminatedString(span) => {
+                write!(f, "{}: missing end of string literal", span)
+            }
+            LexError::MalformedNumber(lexeme, span) => {
+                write!(f, "{}: malformed number {:?}", span, lexeme)
+            }
+            LexError::NumberOverflow(lexeme, span) => {
+                write!(f, "{}: number {:?} is out of range", span, lexeme)
+            }
+            LexError::InvalidEscape(escape, span) => {
+                write!(f, "{}: invalid escape sequence {:?}", span, escape)
+            }
+            LexError::UnterminatedComment(span) => {
+                write!(f, "{}: missing end of block comment", span)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+#[derive(Debug)]
+pub struct KBuff<'a> {
+    pub cur: Option<char>,
+    input: &'a str,
+    chars: Chars<'a>,
+    // Position of `cur` in the input.
+    offset: usize,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> KBuff<'a> {
+    pub fn new(input: &'a str) -> Self {
+        KBuff {
+            cur: Some(' '),
+            input,
+            chars: input.chars(),
+            offset: 0,
+            line: 1,
+            col: 0,
+        }
+    }
+
+    // Lex the whole input, reporting every lexical error rather than just the first.
+    pub fn tokenize(self) -> Result<Vec<Token>, Vec<LexError>> {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        match self.cur {
+            Some('\n') => {
+                self.line += 1;
+                self.col = 1;
+            }
+            Some(_) => self.col += 1,
+            None => {}
+        }
+        self.offset = self.input.len() - self.chars.as_str().len();
+        self.cur = self.chars.next();
+        self.cur
+    }
+
+    // Empty span at the current position, used to mark the start of a token.
+    #[inline]
+    fn mark(&self) -> Span {
+        Span::new(self.offset, self.offset, self.line, self.col)
+    }
+
+    // Span from `start` up to, but not including, the current char.
+    #[inline]
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.offset,
+            ..start
+        }
+    }
+
+    // On error the offending input has already been skipped, so the next call
+    // resumes lexing at the following token.
+    pub fn next_token(&mut self) -> Result<Token, LexError> {
+        use TokenType::*;
+        while let Some(cur) = self.cur {
+            if cur.is_whitespace() {
+                self.consume();
+                continue;
+            }
+
+            let start = self.mark();
+            let token_t = match cur {
+                // Parse complex tokens.
+                x if x.is_numeric() => return self.numeric(start),
+                'r' if self.is_raw_string() => return self.raw_string(start),
+                x if x.is_alphanumeric() => return self.ident(start),
+                // Parse strings.
+                '"' => return self.string(start),
+                // Parse operators.
+                '+' => return self.op(cur, start),
+                '-' => return self.op(cur, start),
+                '*' => return self.op(cur, start),
+                '!' => return self.op(cur, start),
+                '<' => return self.op(cur, start),
+                '>' => return self.op(cur, start),
+                '=' => return self.op(cur, start),
+                '%' => return self.op(cur, start),
+                '&' => return self.op(cur, start),
+                '|' => return self.op(cur, start),
+                '^' => return self.op(cur, start),
+                '/' => return self.op_or_comment(cur, start),
+
+                // Parse single tokens.
+                ',' => Comma,
+                '[' => LBracket,
+                ']' => RBracket,
+                '(' => LParenthesis,
+                ')' => RParenthesis,
+                ';' => Delimiter,
+                '\0' => break,
+                _ => {
+                    self.consume();
+                    return Err(LexError::UnexpectedChar(cur, self.span_from(start)));
+                }
+            };
+            self.consume();
+            return Ok(Token::new(token_t, "".to_owned(), self.span_from(start)));
+        }
+        Ok(Token::new(EOF, "".to_owned(), self.mark()))
+    }
+
+    #[inline]
+    fn numeric(&mut self, start: Span) -> Result<Token, LexError> {
+        let mut lexeme = String::new();
+        let prefixed = self.peek() == '0' && "xXbBoO".contains(self.peek_next());
+        while let Some(cur) = self.cur {
+            // Floating point number.
+            if cur == '.' && self.peek_next().is_alphabetic() {
+                // TODO: This would be a function call on a number, handle accordingly.
+                // Ex: 144.sqrt()
+                self.consume();
+                break;
+            }
+
+            // Sign of an exponent, Ex: 1e-9
+            let exponent_sign =
+                (cur == '+' || cur == '-') && !prefixed && lexeme.ends_with(['e', 'E']);
+
+            // Finished parsing number.
+            if !cur.is_alphanumeric() && cur != '_' && cur != '.' && !exponent_sign {
+                break;
+            }
+
+            lexeme.push(cur);
+            self.consume();
+        }
+
+        // The span only covers the digits, not a trailing method call dot.
+        let span = Span {
+            end: start.start + lexeme.len(),
+            ..start
+        };
+        Number::parse(&lexeme, span)?;
+        Ok(Token::new(TokenType::Numeric, lexeme, span))
+    }
+
+    #[inline]
+    fn ident(&mut self, start: Span) -> Result<Token, LexError> {
+        let mut lexeme = String::new();
+        while let Some(cur) = self.cur {
+            if cur.is_whitespace() {
+                break;
+            }
+
+            if !cur.is_alphanumeric() && cur != '_' {
+                break;
+            }
+
+            lexeme.push(cur);
+            self.consume();
+        }
+        let span = self.span_from(start);
+        Ok(match keyword(&lexeme) {
+            Some(token_t) => Token::new(token_t, "".to_owned(), span),
+            None => Token::new(TokenType::Ident, lexeme, span),
+        })
+    }
+
+    #[inline]
+    fn string(&mut self, start: Span) -> Result<Token, LexError> {
+        self.consume();
+        let mut lexeme = String::new();
+        // Keep going after a bad escape so the whole literal is skipped.
+        let mut error = None;
+        loop {
+            if self.peek() == '"' {
+                break;
+            } else if self.peek() == '\0' {
+                return Err(LexError::UnterminatedString(self.span_from(start)));
+            } else if self.peek() == '\\' {
+                match self.escape() {
+                    Ok(Some(c)) => lexeme.push(c),
+                    Ok(None) => {}
+                    Err(err) => {
+                        error.get_or_insert(err);
+                    }
+                }
+                continue;
+            }
+            lexeme.push(self.cur.unwrap());
+            self.consume();
+        }
+        self.consume();
+        match error {
+            Some(err) => Err(err),
+            None => Ok(Token::new(TokenType::String, lexeme, self.span_from(start))),
+        }
+    }
+
+    // Cook the escape sequence starting at the backslash in `cur`.
+    // A backslash before a newline continues the string on the next line, skipping indentation.
+    #[inline]
+    fn escape(&mut self) -> Result<Option<char>, LexError> {
+        let start = self.mark();
+        self.consume();
+        let c = match self.peek() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => return self.unicode_escape(start).map(Some),
+            '\n' => {
+                while self.peek().is_whitespace() {
+                    self.consume();
+                }
+                return Ok(None);
+            }
+            // Unterminated, reported by the caller.
+            '\0' => return Ok(None),
+            _ => {
+                self.consume();
+                return Err(self.invalid_escape(start));
+            }
+        };
+        self.consume();
+        Ok(Some(c))
+    }
+
+    // Ex: \u{1F600}
+    #[inline]
+    fn unicode_escape(&mut self, start: Span) -> Result<char, LexError> {
+        self.consume();
+        if self.peek() != '{' {
+            return Err(self.invalid_escape(start));
+        }
+        self.consume();
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() {
+            digits.push(self.peek());
+            self.consume();
+        }
+        if self.peek() != '}' {
+            return Err(self.invalid_escape(start));
+        }
+        self.consume();
+        match u32::from_str_radix(&digits, 16) {
+            Ok(value) if digits.len() <= 6 => {
+                char::from_u32(value).ok_or_else(|| self.invalid_escape(start))
+            }
+            _ => Err(self.invalid_escape(start)),
+        }
+    }
+
+    #[inline]
+    fn invalid_escape(&self, start: Span) -> LexError {
+        let escape = self.input[start.start..self.offset].to_owned();
+        LexError::InvalidEscape(escape, self.span_from(start))
+    }
+
+    // `cur` is an `r` that starts a raw string, Ex: r"..." or r#"..."#
+    #[inline]
+    fn is_raw_string(&self) -> bool {
+        self.peek() == 'r' && self.chars.clone().find(|c| *c != '#') == Some('"')
+    }
+
+    #[inline]
+    fn raw_string(&mut self, start: Span) -> Result<Token, LexError> {
+        self.consume();
+        let mut hashes = 0;
+        while self.peek() == '#' {
+            hashes += 1;
+            self.consume();
+        }
+        self.consume();
+
+        let mut lexeme = String::new();
+        loop {
+            match self.cur {
+                None => return Err(LexError::UnterminatedString(self.span_from(start))),
+                Some('"') if self.chars.clone().take_while(|c| *c == '#').count() >= hashes => {
+                    break
+                }
+                Some(c) => lexeme.push(c),
+            }
+            self.consume();
+        }
+        for _ in 0..=hashes {
+            self.consume();
+        }
+        Ok(Token::new(TokenType::String, lexeme, self.span_from(start)))
+    }
+
+    // Maximal munch, Ex: `<<=` is one operator, not `<<` followed by `=`.
+    #[inline]
+    fn op(&mut self, cur: char, start: Span) -> Result<Token, LexError> {
+        let rest: String = std::iter::once(cur)
+            .chain(self.chars.clone().take(2))
+            .collect();
+        let op = OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(*op))
+            .unwrap_or_else(|| unreachable!("{:?} is not an operator", cur));
+        for _ in 0..op.len() {
+            self.consume();
+        }
+
+        let span = self.span_from(start);
+        match *op {
+            "->" => Ok(Token::new(TokenType::Arrow, "".to_owned(), span)),
+            _ => Ok(Token::new(TokenType::Operator, op.to_string(), span)),
+        }
+    }
+
+    #[inline]
+    fn op_or_comment(&mut self, cur: char, start: Span) -> Result<Token, LexError> {
+        if self.peek_next() != '/' && self.peek_next() != '*' {
+            return self.op(cur, start);
+        }
+        self.consume();
+        let mut lexeme = String::new();
+        lexeme.push(cur);
+        match self.peek() {
+            '*' => return self.block_comment(start),
+            _ => {
+                lexeme.push('/');
+                self.consume();
+            }
+        }
+
+        // Doc comment, Ex: /// Adds two numbers. `////` is a plain comment.
+        if self.peek() == '/' && self.peek_next() != '/' {
+            self.consume();
+            if self.peek() == ' ' {
+                self.consume();
+            }
+            let mut doc = String::new();
+            while let Some(cur) = self.cur {
+                if cur == '\n' {
+                    break;
+                }
+                doc.push(cur);
+                self.consume();
+            }
+            return Ok(Token::new(
+                TokenType::DocComment,
+                doc,
+                self.span_from(start),
+            ));
+        }
+
+        while let Some(cur) = self.cur {
+            if cur == '\n' {
+                break;
+            }
+            lexeme.push(cur);
+            self.consume();
+        }
+        Ok(Token::new(
+            TokenType::Comment,
+            lexeme,
+            self.span_from(start),
+        ))
+    }
+
+    // Block comments nest, Ex: /* outer /* inner */ still comment */
+    #[inline]
+    fn block_comment(&mut self, start: Span) -> Result<Token, LexError> {
+        let mut lexeme = "/*".to_owned();
+        let mut depth = 1;
+        self.consume();
+        while let Some(cur) = self.cur {
+            lexeme.push(cur);
+            self.consume();
+            match (cur, self.peek()) {
+                ('/', '*') => depth += 1,
+                ('*', '/') => depth -= 1,
+                _ => continue,
+            }
+            lexeme.push(self.peek());
+            self.consume();
+            if depth == 0 {
+                return Ok(Token::new(
+                    TokenType::Comment,
+                    lexeme,
+                    self.span_from(start),
+                ));
+            }
+        }
+        Err(LexError::UnterminatedComment(self.span_from(start)))
+    }
+
+    #[inline]
+    fn peek(&self) -> char {
+        self.cur.unwrap_or('\0')
+    }
+
+    // The char after `cur`, without consuming anything.
+    #[inline]
+    fn peek_next(&self) -> char {
+        self.chars.clone().next().unwrap_or('\0')
+    }
+}
+
+impl<'a> Iterator for KBuff<'a> {
+    type Item = Result<Token, LexError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(Token {
+                token_t: TokenType::EOF,
+                ..
+            }) => None,
+            result => Some(result),
+        }
+    }
+}
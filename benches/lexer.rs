@@ -0,0 +1,64 @@
+// Throughput of the borrowing lexer against the owned-lexeme lexer it replaced.
+// Run with `cargo bench`.
+
+mod baseline;
+
+use k_lang::lexer::KBuff;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+
+// A large generated K file.
+fn source() -> String {
+    let mut src = String::new();
+    for i in 0..20_000 {
+        src.push_str(&format!(
+            "/// Generated function {i}.\ndef func_{i}(alpha, beta, gamma) alpha * {i}.5 + beta - gamma / 0x{i:x} // {i}\nextern ext_{i}(\"name_{i}\")\n",
+        ));
+    }
+    src
+}
+
+// Both paths clone every token once, as `Parser::token` does.
+fn borrowed(src: &str) -> usize {
+    let mut count = 0;
+    for token in KBuff::new(src) {
+        black_box(token.unwrap().clone());
+        count += 1;
+    }
+    count
+}
+
+fn owned(src: &str) -> usize {
+    let mut count = 0;
+    for token in baseline::KBuff::new(src) {
+        black_box(token.unwrap().clone());
+        count += 1;
+    }
+    count
+}
+
+fn bench(name: &str, src: &str, lex: fn(&str) -> usize) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        black_box(lex(black_box(src)));
+        best = best.min(start.elapsed());
+    }
+    let throughput = src.len() as f64 / best.as_secs_f64() / (1024.0 * 1024.0);
+    println!("{:<10} {:>10.2?} {:>10.1} MiB/s", name, best, throughput);
+    best
+}
+
+fn main() {
+    let src = source();
+    assert_eq!(borrowed(&src), owned(&src));
+    println!("lexing {} KiB, best of {}", src.len() / 1024, ITERATIONS);
+    let borrowed = bench("borrowed", &src, borrowed);
+    let owned = bench("owned", &src, owned);
+    println!(
+        "speedup    {:.2}x",
+        owned.as_secs_f64() / borrowed.as_secs_f64()
+    );
+}
@@ -0,0 +1,77 @@
+use super::{LexWarning, Span};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+use unicode_security::{skeleton, MixedScript};
+
+// UAX #31 identifiers, with a leading underscore allowed as well.
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+pub fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+// NFC form of an identifier, so visually identical names compare equal.
+pub fn normalize(ident: &str) -> Cow<'_, str> {
+    if ident.is_ascii() || is_nfc_quick(ident.chars()) == IsNormalized::Yes {
+        return Cow::Borrowed(ident);
+    }
+    Cow::Owned(ident.nfc().collect())
+}
+
+// Identifiers seen so far keyed by their confusable skeleton (UTS #39). Skeletons are
+// only worked out once a non-ASCII identifier shows up, until then ASCII ones are just kept.
+#[derive(Debug, Default)]
+pub struct Confusables {
+    // ASCII identifiers, one per line. Every identifier is appended, a copy being much cheaper
+    // than a set lookup, and repeats are only dropped once the list passes a megabyte and
+    // has doubled since.
+    ascii: String,
+    // Length of `ascii` when repeats were last dropped.
+    unique: usize,
+    skeletons: Option<HashMap<String, String>>,
+}
+
+impl Confusables {
+    pub fn check(&mut self, ident: &str, span: Span, warnings: &mut Vec<LexWarning>) {
+        if ident.is_ascii() && self.skeletons.is_none() {
+            self.ascii.push_str(ident);
+            self.ascii.push('\n');
+            if self.ascii.len() > 2 * self.unique.max(1 << 20) {
+                let names: HashSet<&str> = self.ascii.lines().collect();
+                let ascii: String = names.into_iter().flat_map(|name| [name, "\n"]).collect();
+                self.unique = ascii.len();
+                self.ascii = ascii;
+            }
+            return;
+        }
+
+        if !ident.is_single_script() {
+            warnings.push(LexWarning::MixedScript(ident.to_owned(), span));
+        }
+
+        let ascii = std::mem::take(&mut self.ascii);
+        let skeletons = self.skeletons.get_or_insert_with(|| {
+            ascii
+                .lines()
+                .map(|name| (skeleton(name).collect(), name.to_owned()))
+                .collect()
+        });
+        let skeleton: String = skeleton(ident).collect();
+        match skeletons.get(&skeleton) {
+            // Two ASCII names that merely look alike, Ex: `rn` and `m`, are left alone.
+            Some(other) if other != ident && !(other.is_ascii() && ident.is_ascii()) => warnings
+                .push(LexWarning::Confusable(
+                    ident.to_owned(),
+                    other.clone(),
+                    span,
+                )),
+            Some(_) => {}
+            None => {
+                skeletons.insert(skeleton, ident.to_owned());
+            }
+        }
+    }
+}
@@ -0,0 +1,1549 @@
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::iter::Peekable;
+use std::str::Chars;
+
+mod ident;
+mod stream;
+mod trivia;
+use ident::{is_ident_continue, is_ident_start, normalize, Confusables};
+pub use stream::KStream;
+pub use trivia::{LosslessToken, Trivia, TriviaKind};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TokenType {
+    Def,
+    Extern,
+    If,
+    Then,
+    Else,
+    For,
+    In,
+    While,
+    Var,
+    Let,
+    Return,
+    True,
+    False,
+    Delimiter,
+    LParenthesis,
+    RParenthesis,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Comma,
+    Dot,
+    Colon,
+    Comment,
+    DocComment,
+    Ident,
+    String,
+    Numeric,
+    Operator,
+    Arrow,
+    EOF,
+}
+
+// Reserved words, shared by everything that needs to know what a keyword is.
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("def", TokenType::Def),
+    ("extern", TokenType::Extern),
+    ("if", TokenType::If),
+    ("then", TokenType::Then),
+    ("else", TokenType::Else),
+    ("for", TokenType::For),
+    ("in", TokenType::In),
+    ("while", TokenType::While),
+    ("var", TokenType::Var),
+    ("let", TokenType::Let),
+    ("return", TokenType::Return),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+];
+
+pub fn keyword(ident: &str) -> Option<TokenType> {
+    KEYWORDS
+        .iter()
+        .find(|(name, _)| *name == ident)
+        .map(|(_, token_t)| *token_t)
+}
+
+// Every operator the lexer knows, longest first so `op` can match greedily.
+pub const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "->", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "+", "-", "*", "/", "%", "!", "<", ">", "=", "&", "|", "^",
+];
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Self {
+        Span {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    // From the start of `self` to the end of `end`.
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            end: end.end,
+            ..self
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+// Lexemes borrow from the input unless they had to be cooked, Ex: strings with escapes.
+#[derive(PartialEq, Clone)]
+pub struct Token<'a> {
+    pub token_t: TokenType,
+    pub lexeme: Cow<'a, str>,
+    pub span: Span,
+    // Typed value of a `Numeric` token, parsed once by the lexer.
+    pub value: Option<Number>,
+}
+
+impl<'a> Token<'a> {
+    pub fn new(token_t: TokenType, lexeme: impl Into<Cow<'a, str>>, span: Span) -> Self {
+        Token {
+            token_t,
+            lexeme: lexeme.into(),
+            span,
+            value: None,
+        }
+    }
+
+    // Detach the token from the input it was lexed from.
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            token_t: self.token_t,
+            lexeme: Cow::Owned(self.lexeme.into_owned()),
+            span: self.span,
+            value: self.value,
+        }
+    }
+}
+
+impl<'a> Debug for Token<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", &self.to_string())
+    }
+}
+
+impl<'a> Display for Token<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<| type: {:?} + lexeme: {:?} + at: {} |>",
+            self.token_t, self.lexeme, self.span
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    // Parse a numeric literal as written in the source, Ex: 0xFF, 0b1010, 0o17, 1_000, 1.5e-9
+    pub fn parse(lexeme: &str, span: Span) -> Result<Number, LexError> {
+        let malformed = || LexError::MalformedNumber(lexeme.to_owned(), span);
+        let overflow = || LexError::NumberOverflow(lexeme.to_owned(), span);
+
+        let radix = match lexeme.get(..2) {
+            Some("0x") | Some("0X") => 16,
+            Some("0o") | Some("0O") => 8,
+            Some("0b") | Some("0B") => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            let digits = lexeme[2..].replace('_', "");
+            if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+                return Err(malformed());
+            }
+            return i64::from_str_radix(&digits, radix)
+                .map(Number::Int)
+                .map_err(|_| overflow());
+        }
+
+        // Decimal: digits [. digits] [e [+-] digits]
+        let mut chars = lexeme.chars().peekable();
+        let digits = |chars: &mut Peekable<Chars>| {
+            let mut found = false;
+            while let Some(c) = chars.peek() {
+                match c {
+                    '0'..='9' => found = true,
+                    '_' => {}
+                    _ => break,
+                }
+                chars.next();
+            }
+            found
+        };
+        let mut is_float = false;
+        if !digits(&mut chars) {
+            return Err(malformed());
+        }
+        if chars.peek() == Some(&'.') {
+            is_float = true;
+            chars.next();
+            digits(&mut chars);
+        }
+        if let Some('e') | Some('E') = chars.peek() {
+            is_float = true;
+            chars.next();
+            if let Some('+') | Some('-') = chars.peek() {
+                chars.next();
+            }
+            if !digits(&mut chars) {
+                return Err(malformed());
+            }
+        }
+        if chars.next().is_some() {
+            return Err(malformed());
+        }
+
+        let text = lexeme.replace('_', "");
+        if is_float {
+            match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(Number::Float(value)),
+                Ok(_) => Err(overflow()),
+                Err(_) => Err(malformed()),
+            }
+        } else {
+            text.parse::<i64>().map(Number::Int).map_err(|_| overflow())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexError {
+    UnexpectedChar(char, Span),
+    UnterminatedString(Span),
+    MalformedNumber(String, Span),
+    NumberOverflow(String, Span),
+    InvalidEscape(String, Span),
+    UnterminatedComment(Span),
+    InvalidUtf8(Span),
+    Io(String, Span),
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedChar(_, span) => *span,
+            LexError::UnterminatedString(span) => *span,
+            LexError::MalformedNumber(_, span) => *span,
+            LexError::NumberOverflow(_, span) => *span,
+            LexError::InvalidEscape(_, span) => *span,
+            LexError::UnterminatedComment(span) => *span,
+            LexError::InvalidUtf8(span) => *span,
+            LexError::Io(_, span) => *span,
+        }
+    }
+
+    pub(crate) fn span_mut(&mut self) -> &mut Span {
+        match self {
+            LexError::UnexpectedChar(_, span) => span,
+            LexError::UnterminatedString(span) => span,
+            LexError::MalformedNumber(_, span) => span,
+            LexError::NumberOverflow(_, span) => span,
+            LexError::InvalidEscape(_, span) => span,
+            LexError::UnterminatedComment(span) => span,
+            LexError::InvalidUtf8(span) => span,
+            LexError::Io(_, span) => span,
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LexError::UnexpectedChar(c, span) => {
+                write!(f, "{}: unexpected character {:?}", span, c)
+            }
+            LexError::UnterminatedString(span) => {
+                write!(f, "{}: missing end of string literal", span)
+            }
+            LexError::MalformedNumber(lexeme, span) => {
+                write!(f, "{}: malformed number {:?}", span, lexeme)
+            }
+            LexError::NumberOverflow(lexeme, span) => {
+                write!(f, "{}: number {:?} is out of range", span, lexeme)
+            }
+            LexError::InvalidEscape(escape, span) => {
+                write!(f, "{}: invalid escape sequence {:?}", span, escape)
+            }
+            LexError::UnterminatedComment(span) => {
+                write!(f, "{}: missing end of block comment", span)
+            }
+            LexError::InvalidUtf8(span) => write!(f, "{}: input is not valid UTF-8", span),
+            LexError::Io(message, span) => write!(f, "{}: error reading input: {}", span, message),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+// Suspicious but valid input, the token is still produced.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexWarning {
+    MixedScript(String, Span),
+    // An identifier that looks like an earlier, different one.
+    Confusable(String, String, Span),
+}
+
+impl LexWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            LexWarning::MixedScript(_, span) => *span,
+            LexWarning::Confusable(_, _, span) => *span,
+        }
+    }
+}
+
+impl Display for LexWarning {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LexWarning::MixedScript(ident, span) => {
+                write!(f, "{}: identifier {:?} mixes scripts", span, ident)
+            }
+            LexWarning::Confusable(ident, other, span) => write!(
+                f,
+                "{}: identifier {:?} is confusable with {:?}",
+                span, ident, other
+            ),
+        }
+    }
+}
+
+// Anything the parser can pull tokens from.
+pub trait TokenStream<'a> {
+    fn next_token(&mut self) -> Result<Token<'a>, LexError>;
+
+    // Confusable and mixed-script identifiers lexed so far.
+    fn warnings(&self) -> &[LexWarning];
+}
+
+#[derive(Debug)]
+pub struct KBuff<'a> {
+    pub cur: Option<char>,
+    input: &'a str,
+    chars: Chars<'a>,
+    // Position of `cur` in the input.
+    offset: usize,
+    line: usize,
+    col: usize,
+    // Set once a look past `cur` runs out of input, a stream may have more to read.
+    looked_past_end: bool,
+    warnings: Vec<LexWarning>,
+    confusables: Confusables,
+}
+
+impl<'a> KBuff<'a> {
+    pub fn new(input: &'a str) -> Self {
+        KBuff {
+            cur: Some(' '),
+            input,
+            chars: input.chars(),
+            offset: 0,
+            line: 1,
+            col: 0,
+            looked_past_end: false,
+            warnings: Vec::new(),
+            confusables: Confusables::default(),
+        }
+    }
+
+    pub fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+
+    // Lex the whole input, reporting every lexical error rather than just the first,
+    // along with any warnings.
+    pub fn tokenize(mut self) -> (Result<Vec<Token<'a>>, Vec<LexError>>, Vec<LexWarning>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+        let result = if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        };
+        (result, self.warnings)
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        match self.cur {
+            Some('\n') => {
+                self.line += 1;
+                self.col = 1;
+            }
+            Some(_) => self.col += 1,
+            None => {}
+        }
+        self.offset = self.input.len() - self.chars.as_str().len();
+        self.cur = self.chars.next();
+        self.cur
+    }
+
+    // Empty span at the current position, used to mark the start of a token.
+    #[inline]
+    fn mark(&self) -> Span {
+        Span::new(self.offset, self.offset, self.line, self.col)
+    }
+
+    // Span from `start` up to, but not including, the current char.
+    #[inline]
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.offset,
+            ..start
+        }
+    }
+
+    // Input from `start` up to, but not including, the current char.
+    #[inline]
+    fn slice_from(&self, start: usize) -> &'a str {
+        let input = self.input;
+        &input[start..self.offset]
+    }
+
+    // On error the offending input has already been skipped, so the next call
+    // resumes lexing at the following token.
+    pub fn next_token(&mut self) -> Result<Token<'a>, LexError> {
+        use TokenType::*;
+        while let Some(cur) = self.cur {
+            if cur.is_whitespace() {
+                self.consume();
+                continue;
+            }
+
+            let start = self.mark();
+            let token_t = match cur {
+                // Parse complex tokens.
+                x if x.is_ascii_digit() => return self.numeric(start),
+                'r' if self.is_raw_string() => return self.raw_string(start),
+                x if is_ident_start(x) => return self.ident(start),
+                // Parse strings.
+                '"' => return self.string(start),
+                // Parse operators.
+                '+' => return self.op(cur, start),
+                '-' => return self.op(cur, start),
+                '*' => return self.op(cur, start),
+                '!' => return self.op(cur, start),
+                '<' => return self.op(cur, start),
+                '>' => return self.op(cur, start),
+                '=' => return self.op(cur, start),
+                '%' => return self.op(cur, start),
+                '&' => return self.op(cur, start),
+                '|' => return self.op(cur, start),
+                '^' => return self.op(cur, start),
+                '/' => return self.op_or_comment(cur, start),
+
+                // Parse single tokens.
+                ',' => Comma,
+                '.' => Dot,
+                ':' => Colon,
+                '[' => LBracket,
+                ']' => RBracket,
+                '{' => LBrace,
+                '}' => RBrace,
+                '(' => LParenthesis,
+                ')' => RParenthesis,
+                ';' => Delimiter,
+                _ => {
+                    self.consume();
+                    return Err(LexError::UnexpectedChar(cur, self.span_from(start)));
+                }
+            };
+            self.consume();
+            return Ok(Token::new(token_t, "", self.span_from(start)));
+        }
+        Ok(Token::new(EOF, "", self.mark()))
+    }
+
+    #[inline]
+    fn numeric(&mut self, start: Span) -> Result<Token<'a>, LexError> {
+        let prefixed = self.peek() == '0' && "xXbBoO".contains(self.peek_next());
+        let mut prev = '\0';
+        while let Some(cur) = self.cur {
+            // A method call on the number rather than a fraction, Ex: 144.sqrt(), unless
+            // it is an exponent, Ex: 1.e5
+            if cur == '.' && is_ident_start(self.peek_next()) && !self.is_exponent_after_dot() {
+                break;
+            }
+
+            // Sign of an exponent, Ex: 1e-9
+            let exponent_sign =
+                (cur == '+' || cur == '-') && !prefixed && (prev == 'e' || prev == 'E');
+
+            // Finished parsing number.
+            if !cur.is_alphanumeric() && cur != '_' && cur != '.' && !exponent_sign {
+                break;
+            }
+
+            prev = cur;
+            self.consume();
+        }
+
+        let span = self.span_from(start);
+        let lexeme = self.slice_from(start.start);
+        let mut token = Token::new(TokenType::Numeric, lexeme, span);
+        token.value = Some(Number::parse(lexeme, span)?);
+        Ok(token)
+    }
+
+    #[inline]
+    fn ident(&mut self, start: Span) -> Result<Token<'a>, LexError> {
+        while let Some(cur) = self.cur {
+            if !is_ident_continue(cur) {
+                break;
+            }
+
+            self.consume();
+        }
+        let span = self.span_from(start);
+        let lexeme = normalize(self.slice_from(start.start));
+        if let Some(token_t) = keyword(&lexeme) {
+            return Ok(Token::new(token_t, "", span));
+        }
+        self.confusables.check(&lexeme, span, &mut self.warnings);
+        Ok(Token::new(TokenType::Ident, lexeme, span))
+    }
+
+    #[inline]
+    fn string(&mut self, start: Span) -> Result<Token<'a>, LexError> {
+        self.consume();
+        let content = self.offset;
+        // Only copied once an escape has to be cooked.
+        let mut cooked: Option<String> = None;
+        // Keep going after a bad escape so the whole literal is skipped.
+        let mut error = None;
+        loop {
+            if self.peek() == '"' {
+                break;
+            } else if self.cur.is_none() {
+                return Err(LexError::UnterminatedString(self.span_from(start)));
+            } else if self.peek() == '\\' {
+                let input = self.input;
+                let offset = self.offset;
+                let cooked = cooked.get_or_insert_with(|| input[content..offset].to_owned());
+                match self.escape() {
+                    Ok(Some(c)) => cooked.push(c),
+                    Ok(None) => {}
+                    Err(err) => {
+                        error.get_or_insert(err);
+                    }
+                }
+                continue;
+            }
+            if let Some(cooked) = &mut cooked {
+                cooked.push(self.peek());
+            }
+            self.consume();
+        }
+        let lexeme = match cooked {
+            Some(cooked) => Cow::Owned(cooked),
+            None => Cow::Borrowed(self.slice_from(content)),
+        };
+        self.consume();
+        match error {
+            Some(err) => Err(err),
+            None => Ok(Token::new(TokenType::String, lexeme, self.span_from(start))),
+        }
+    }
+
+    // Cook the escape sequence starting at the backslash in `cur`.
+    // A backslash before a newline continues the string on the next line, skipping indentation.
+    #[inline]
+    fn escape(&mut self) -> Result<Option<char>, LexError> {
+        let start = self.mark();
+        self.consume();
+        // Unterminated, reported by the caller.
+        if self.cur.is_none() {
+            return Ok(None);
+        }
+        let c = match self.peek() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => return self.unicode_escape(start).map(Some),
+            '\n' | '\r' if self.peek() == '\n' || self.peek_next() == '\n' => {
+                while self.peek().is_whitespace() {
+                    self.consume();
+                }
+                return Ok(None);
+            }
+            _ => {
+                self.consume();
+                return Err(self.invalid_escape(start));
+            }
+        };
+        self.consume();
+        Ok(Some(c))
+    }
+
+    // Ex: \u{1F600}
+    #[inline]
+    fn unicode_escape(&mut self, start: Span) -> Result<char, LexError> {
+        self.consume();
+        if self.peek() != '{' {
+            return Err(self.invalid_escape(start));
+        }
+        self.consume();
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() {
+            digits.push(self.peek());
+            self.consume();
+        }
+        if self.peek() != '}' {
+            return Err(self.invalid_escape(start));
+        }
+        self.consume();
+        match u32::from_str_radix(&digits, 16) {
+            Ok(value) if digits.len() <= 6 => {
+                char::from_u32(value).ok_or_else(|| self.invalid_escape(start))
+            }
+            _ => Err(self.invalid_escape(start)),
+        }
+    }
+
+    #[inline]
+    fn invalid_escape(&self, start: Span) -> LexError {
+        let escape = self.input[start.start..self.offset].to_owned();
+        LexError::InvalidEscape(escape, self.span_from(start))
+    }
+
+    // `cur` is a `.` followed by an exponent, Ex: 1.e5 or 2.E-3
+    #[inline]
+    fn is_exponent_after_dot(&mut self) -> bool {
+        let mut rest = self.chars.clone();
+        let (marker, after) = (rest.next(), rest.next());
+        self.looked_past_end |= after.is_none();
+        matches!(marker, Some('e' | 'E')) && matches!(after, Some('0'..='9' | '+' | '-'))
+    }
+
+    // `cur` is an `r` that starts a raw string, Ex: r"..." or r#"..."#
+    #[inline]
+    fn is_raw_string(&mut self) -> bool {
+        let after = self.chars.clone().find(|c| *c != '#');
+        self.looked_past_end |= after.is_none();
+        self.peek() == 'r' && after == Some('"')
+    }
+
+    #[inline]
+    fn raw_string(&mut self, start: Span) -> Result<Token<'a>, LexError> {
+        self.consume();
+        let mut hashes = 0;
+        while self.peek() == '#' {
+            hashes += 1;
+            self.consume();
+        }
+        self.consume();
+
+        let content = self.offset;
+        loop {
+            match self.cur {
+                None => return Err(LexError::UnterminatedString(self.span_from(start))),
+                Some('"') if self.chars.clone().take_while(|c| *c == '#').count() >= hashes => {
+                    break
+                }
+                Some(_) => {}
+            }
+            self.consume();
+        }
+        let lexeme = self.slice_from(content);
+        for _ in 0..=hashes {
+            self.consume();
+        }
+        Ok(Token::new(TokenType::String, lexeme, self.span_from(start)))
+    }
+
+    // Maximal munch, Ex: `<<=` is one operator, not `<<` followed by `=`.
+    #[inline]
+    fn op(&mut self, cur: char, start: Span) -> Result<Token<'a>, LexError> {
+        let rest: String = std::iter::once(cur)
+            .chain(self.chars.clone().take(2))
+            .collect();
+        self.looked_past_end |= rest.chars().count() < 3;
+        let op = OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(*op))
+            .unwrap_or_else(|| unreachable!("{:?} is not an operator", cur));
+        for _ in 0..op.len() {
+            self.consume();
+        }
+
+        let span = self.span_from(start);
+        match *op {
+            "->" => Ok(Token::new(TokenType::Arrow, "", span)),
+            _ => Ok(Token::new(TokenType::Operator, *op, span)),
+        }
+    }
+
+    #[inline]
+    fn op_or_comment(&mut self, cur: char, start: Span) -> Result<Token<'a>, LexError> {
+        if self.peek_next() != '/' && self.peek_next() != '*' {
+            return self.op(cur, start);
+        }
+        self.consume();
+        if self.peek() == '*' {
+            return self.block_comment(start);
+        }
+        self.consume();
+
+        // Doc comment, Ex: /// Adds two numbers. `////` is a plain comment.
+        if self.peek() == '/' && self.peek_next() != '/' {
+            self.consume();
+            if self.peek() == ' ' {
+                self.consume();
+            }
+            let doc = self.offset;
+            while let Some(cur) = self.cur {
+                if cur == '\n' {
+                    break;
+                }
+                self.consume();
+            }
+            // The `\r` of a CRLF line ending isn't part of the text.
+            let text = self.slice_from(doc);
+            return Ok(Token::new(
+                TokenType::DocComment,
+                text.strip_suffix('\r').unwrap_or(text),
+                self.span_from(start),
+            ));
+        }
+
+        while let Some(cur) = self.cur {
+            if cur == '\n' {
+                break;
+            }
+            self.consume();
+        }
+        Ok(Token::new(
+            TokenType::Comment,
+            self.slice_from(start.start),
+            self.span_from(start),
+        ))
+    }
+
+    // Block comments nest, Ex: /* outer /* inner */ still comment */
+    #[inline]
+    fn block_comment(&mut self, start: Span) -> Result<Token<'a>, LexError> {
+        let mut depth = 1;
+        self.consume();
+        while let Some(cur) = self.cur {
+            self.consume();
+            match (cur, self.peek()) {
+                ('/', '*') => depth += 1,
+                ('*', '/') => depth -= 1,
+                _ => continue,
+            }
+            self.consume();
+            if depth == 0 {
+                return Ok(Token::new(
+                    TokenType::Comment,
+                    self.slice_from(start.start),
+                    self.span_from(start),
+                ));
+            }
+        }
+        Err(LexError::UnterminatedComment(self.span_from(start)))
+    }
+
+    #[inline]
+    fn peek(&self) -> char {
+        self.cur.unwrap_or('\0')
+    }
+
+    // The char after `cur`, without consuming anything.
+    #[inline]
+    fn peek_next(&mut self) -> char {
+        let next = self.chars.clone().next();
+        self.looked_past_end |= next.is_none();
+        next.unwrap_or('\0')
+    }
+}
+
+impl<'a> TokenStream<'a> for KBuff<'a> {
+    fn next_token(&mut self) -> Result<Token<'a>, LexError> {
+        KBuff::next_token(self)
+    }
+
+    fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+}
+
+impl<'a> Iterator for KBuff<'a> {
+    type Item = Result<Token<'a>, LexError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(Token {
+                token_t: TokenType::EOF,
+                ..
+            }) => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenType::*;
+
+    // A `Numeric` token as the lexer makes it, value included.
+    fn numeric(lexeme: &str, span: Span) -> Token<'_> {
+        let mut token = Token::new(Numeric, lexeme, span);
+        token.value = Number::parse(lexeme, span).ok();
+        token
+    }
+
+    #[test]
+    fn test_get_next_char() {
+        let mut buf = KBuff::new("def");
+        assert_eq!(buf.consume().unwrap(), 'd');
+        assert_eq!(buf.consume().unwrap(), 'e');
+        assert_eq!(buf.consume().unwrap(), 'f');
+        assert_eq!(buf.consume(), None);
+        let mut buf = KBuff::new("extern");
+        assert_eq!(buf.consume().unwrap(), 'e');
+        assert_eq!(buf.consume().unwrap(), 'x');
+        assert_eq!(buf.consume().unwrap(), 't');
+        assert_eq!(buf.consume().unwrap(), 'e');
+        assert_eq!(buf.consume().unwrap(), 'r');
+        assert_eq!(buf.consume().unwrap(), 'n');
+    }
+
+    #[test]
+    fn test_parse_tokens() {
+        let mut buf = KBuff::new("def");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(Def, "".to_owned(), Span::new(0, 3, 1, 1)));
+        let mut buf = KBuff::new("foo");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "foo".to_owned(), Span::new(0, 3, 1, 1))
+        );
+
+        let mut buf = KBuff::new("extern");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Extern, "".to_owned(), Span::new(0, 6, 1, 1))
+        );
+        let mut buf = KBuff::new(",");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(Comma, "".to_owned(), Span::new(0, 1, 1, 1)));
+        let mut buf = KBuff::new(";");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Delimiter, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("(");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LParenthesis, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new(")");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RParenthesis, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("[");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LBracket, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("]");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RBracket, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new(":");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(Colon, "".to_owned(), Span::new(0, 1, 1, 1)));
+        let mut buf = KBuff::new("{");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LBrace, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("}");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RBrace, "".to_owned(), Span::new(0, 1, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_consecutive_tokens() {
+        let mut buf = KBuff::new("def foo(x, y) extern, ; ()[]");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(Def, "".to_owned(), Span::new(0, 3, 1, 1)));
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "foo".to_owned(), Span::new(4, 7, 1, 5))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LParenthesis, "".to_owned(), Span::new(7, 8, 1, 8))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "x".to_owned(), Span::new(8, 9, 1, 9))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Comma, "".to_owned(), Span::new(9, 10, 1, 10))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "y".to_owned(), Span::new(11, 12, 1, 12))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RParenthesis, "".to_owned(), Span::new(12, 13, 1, 13))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Extern, "".to_owned(), Span::new(14, 20, 1, 15))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Comma, "".to_owned(), Span::new(20, 21, 1, 21))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Delimiter, "".to_owned(), Span::new(22, 23, 1, 23))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LParenthesis, "".to_owned(), Span::new(24, 25, 1, 25))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RParenthesis, "".to_owned(), Span::new(25, 26, 1, 26))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LBracket, "".to_owned(), Span::new(26, 27, 1, 27))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RBracket, "".to_owned(), Span::new(27, 28, 1, 28))
+        );
+    }
+
+    #[test]
+    fn test_parse_num() {
+        let mut buf = KBuff::new("10");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("10", Span::new(0, 2, 1, 1)));
+        let mut buf = KBuff::new("20");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("20", Span::new(0, 2, 1, 1)));
+        let mut buf = KBuff::new("20.");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("20.", Span::new(0, 3, 1, 1)));
+        let mut buf = KBuff::new("0.20");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("0.20", Span::new(0, 4, 1, 1)));
+        let mut buf = KBuff::new("23.4");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("23.4", Span::new(0, 4, 1, 1)));
+    }
+
+    #[test]
+    fn test_invalid_float_number() {
+        let mut buf = KBuff::new(".10");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(Dot, "".to_owned(), Span::new(0, 1, 1, 1)));
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("10", Span::new(1, 3, 1, 2)));
+        let mut buf = KBuff::new("1k0");
+        let err = buf.next_token();
+        assert_eq!(
+            err,
+            Err(LexError::MalformedNumber(
+                "1k0".to_owned(),
+                Span::new(0, 3, 1, 1)
+            ))
+        );
+        let mut buf = KBuff::new(".1k0");
+        assert_eq!(buf.next_token().unwrap().token_t, Dot);
+        let err = buf.next_token();
+        assert_eq!(
+            err,
+            Err(LexError::MalformedNumber(
+                "1k0".to_owned(),
+                Span::new(1, 4, 1, 2)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_single_char_ops() {
+        let mut buf = KBuff::new("+");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "+".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("-");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "-".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("*");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "*".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("/");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "/".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let mut buf = KBuff::new("=");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "=".to_owned(), Span::new(0, 1, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_string() {
+        let mut buf = KBuff::new("\"HelloWorld\"");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(String, "HelloWorld".to_owned(), Span::new(0, 12, 1, 1))
+        );
+
+        let mut buf = KBuff::new("def hello_world() \"HelloWorld\"");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(Def, "".to_owned(), Span::new(0, 3, 1, 1)));
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "hello_world".to_owned(), Span::new(4, 15, 1, 5))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(LParenthesis, "".to_owned(), Span::new(15, 16, 1, 16))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(RParenthesis, "".to_owned(), Span::new(16, 17, 1, 17))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(String, "HelloWorld".to_owned(), Span::new(18, 30, 1, 19))
+        );
+    }
+
+    #[test]
+    fn test_parse_mutli_char_ops() {
+        let mut buf = KBuff::new("!=");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "!=".to_string(), Span::new(0, 2, 1, 1))
+        );
+        let mut buf = KBuff::new("==");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "==".to_string(), Span::new(0, 2, 1, 1))
+        );
+
+        let mut buf = KBuff::new("1 != 2");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("1", Span::new(0, 1, 1, 1)));
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "!=".to_string(), Span::new(2, 4, 1, 3))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("2", Span::new(5, 6, 1, 6)));
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut buf = KBuff::new("x+y");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "x".to_owned(), Span::new(0, 1, 1, 1))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "+".to_owned(), Span::new(1, 2, 1, 2))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "y".to_owned(), Span::new(2, 3, 1, 3))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, Token::new(EOF, "".to_owned(), Span::new(3, 3, 1, 4)));
+
+        let mut buf = KBuff::new("// hi\n  \"a\nb\" é\n\tfoo");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Comment, "// hi".to_owned(), Span::new(0, 5, 1, 1))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(String, "a\nb".to_owned(), Span::new(8, 13, 2, 3))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "é".to_owned(), Span::new(14, 16, 3, 4))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "foo".to_owned(), Span::new(18, 21, 4, 2))
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut buf = KBuff::new("\"Hello");
+        let err = buf.next_token();
+        assert_eq!(
+            err,
+            Err(LexError::UnterminatedString(Span::new(0, 6, 1, 1)))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok.token_t, EOF);
+    }
+
+    #[test]
+    fn test_recover_all_errors() {
+        let buf = KBuff::new("def foo(x) $ x + 2k @ 1");
+        assert_eq!(
+            buf.tokenize().0,
+            Err(vec![
+                LexError::UnexpectedChar('$', Span::new(11, 12, 1, 12)),
+                LexError::MalformedNumber("2k".to_owned(), Span::new(17, 19, 1, 18)),
+                LexError::UnexpectedChar('@', Span::new(20, 21, 1, 21)),
+            ])
+        );
+
+        let tokens = KBuff::new("def foo(x) x + 1").tokenize().0.unwrap();
+        assert_eq!(tokens.len(), 8);
+
+        // NUL is an ordinary char, not the end of input.
+        assert_eq!(
+            KBuff::new("x\0 $ y").tokenize().0,
+            Err(vec![
+                LexError::UnexpectedChar('\0', Span::new(1, 2, 1, 2)),
+                LexError::UnexpectedChar('$', Span::new(3, 4, 1, 4)),
+            ])
+        );
+        let tokens = KBuff::new("\"a\0b\" + 1").tokenize().0.unwrap();
+        assert_eq!(tokens[0].lexeme, "a\0b");
+        assert_eq!(tokens.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_num_forms() {
+        let num = |input| KBuff::new(input).next_token().unwrap().value;
+        assert_eq!(num("10"), Some(Number::Int(10)));
+        assert_eq!(num("20."), Some(Number::Float(20.0)));
+        assert_eq!(num("0xFF"), Some(Number::Int(255)));
+        assert_eq!(num("0b1010"), Some(Number::Int(10)));
+        assert_eq!(num("0o17"), Some(Number::Int(15)));
+        assert_eq!(num("1_000_000"), Some(Number::Int(1_000_000)));
+        assert_eq!(num("1e-9"), Some(Number::Float(1e-9)));
+        assert_eq!(num("2.5E+3"), Some(Number::Float(2.5e3)));
+        assert_eq!(num("0xdead_beef"), Some(Number::Int(0xdead_beef)));
+
+        let mut buf = KBuff::new("1e-9)");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok, numeric("1e-9", Span::new(0, 4, 1, 1)));
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok.token_t, RParenthesis);
+
+        let mut buf = KBuff::new("144.sqrt() 1.5.abs() x.y");
+        let tokens: Vec<_> = buf.by_ref().map(|tok| tok.unwrap()).collect();
+        let lexemes: Vec<_> = tokens.iter().map(|tok| &*tok.lexeme).collect();
+        assert_eq!(
+            lexemes,
+            vec!["144", "", "sqrt", "", "", "1.5", "", "abs", "", "", "x", "", "y"]
+        );
+        assert_eq!(tokens[0].span, Span::new(0, 3, 1, 1));
+        assert_eq!(tokens[1], Token::new(Dot, "", Span::new(3, 4, 1, 4)));
+        assert_eq!(tokens[5].value, Some(Number::Float(1.5)));
+        assert_eq!(tokens[11].token_t, Dot);
+
+        // A fraction with no digits still takes an exponent.
+        assert_eq!(num("1.e5"), Some(Number::Float(1e5)));
+        assert_eq!(num("2.E-3"), Some(Number::Float(2e-3)));
+        let lexemes: Vec<_> = KBuff::new("1.exp() 2.e")
+            .map(|tok| tok.unwrap().lexeme)
+            .collect();
+        assert_eq!(lexemes, vec!["1", "", "exp", "", "", "2", "", "e"]);
+
+        let mut buf = KBuff::new("1-2");
+        assert_eq!(buf.next_token().unwrap().value, Some(Number::Int(1)));
+        assert_eq!(buf.next_token().unwrap().lexeme, "-");
+        assert_eq!(buf.next_token().unwrap().value, Some(Number::Int(2)));
+    }
+
+    #[test]
+    fn test_invalid_num_forms() {
+        let err = |input| KBuff::new(input).next_token().unwrap_err();
+        assert_eq!(
+            err("1.2.3"),
+            LexError::MalformedNumber("1.2.3".to_owned(), Span::new(0, 5, 1, 1))
+        );
+        assert_eq!(
+            err("0x"),
+            LexError::MalformedNumber("0x".to_owned(), Span::new(0, 2, 1, 1))
+        );
+        assert_eq!(
+            err("0b102"),
+            LexError::MalformedNumber("0b102".to_owned(), Span::new(0, 5, 1, 1))
+        );
+        assert_eq!(
+            err("1e"),
+            LexError::MalformedNumber("1e".to_owned(), Span::new(0, 2, 1, 1))
+        );
+        assert_eq!(
+            err("9223372036854775808"),
+            LexError::NumberOverflow("9223372036854775808".to_owned(), Span::new(0, 19, 1, 1))
+        );
+        assert_eq!(
+            err("1e999"),
+            LexError::NumberOverflow("1e999".to_owned(), Span::new(0, 5, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_string_escapes() {
+        let string = |input| KBuff::new(input).next_token().unwrap().lexeme;
+        assert_eq!(string(r#""a\nb\tc""#), "a\nb\tc");
+        assert_eq!(string(r#""say \"hi\"""#), "say \"hi\"");
+        assert_eq!(string(r#""\\ \0 \r \'""#), "\\ \0 \r '");
+        assert_eq!(string(r#""\u{1F600}\u{e9}""#), "\u{1F600}\u{e9}");
+        assert_eq!(string("\"multi\nline\""), "multi\nline");
+        assert_eq!(string("\"one \\\n    two\""), "one two");
+        assert_eq!(string("\"one \\\r\n    two\""), "one two");
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let mut buf = KBuff::new(r#"x "ab\qc" y"#);
+        buf.next_token().unwrap();
+        assert_eq!(
+            buf.next_token(),
+            Err(LexError::InvalidEscape(
+                r"\q".to_owned(),
+                Span::new(5, 7, 1, 6)
+            ))
+        );
+        // The rest of the literal is skipped.
+        assert_eq!(buf.next_token().unwrap().lexeme, "y");
+
+        let err = |input| KBuff::new(input).next_token().unwrap_err();
+        assert_eq!(
+            err(r#""\u{110000}""#),
+            LexError::InvalidEscape(r"\u{110000}".to_owned(), Span::new(1, 11, 1, 2))
+        );
+        assert_eq!(
+            err(r#""\u12""#),
+            LexError::InvalidEscape(r"\u".to_owned(), Span::new(1, 3, 1, 2))
+        );
+        // A NUL char after the backslash isn't the end of the input.
+        assert_eq!(
+            err("\"\\\0\""),
+            LexError::InvalidEscape("\\\0".to_owned(), Span::new(1, 3, 1, 2))
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_string() {
+        let mut buf = KBuff::new(r##"r"C:\path" r#"say "hi""# rest"##);
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(String, r"C:\path".to_owned(), Span::new(0, 10, 1, 1))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(String, r#"say "hi""#.to_owned(), Span::new(11, 24, 1, 12))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok.token_t, Ident);
+
+        let mut buf = KBuff::new(r##"r#"open"##);
+        assert_eq!(
+            buf.next_token(),
+            Err(LexError::UnterminatedString(Span::new(0, 7, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn test_multi_line_string_spans() {
+        let mut buf = KBuff::new("\"a\nb\" c");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(String, "a\nb".to_owned(), Span::new(0, 5, 1, 1))
+        );
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "c".to_owned(), Span::new(6, 7, 2, 4))
+        );
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let mut buf = KBuff::new("/* a /* b */ c */ x // line\n//// bar\ny");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(
+                Comment,
+                "/* a /* b */ c */".to_owned(),
+                Span::new(0, 17, 1, 1)
+            )
+        );
+        assert_eq!(buf.next_token().unwrap().lexeme, "x");
+        assert_eq!(buf.next_token().unwrap().lexeme, "// line");
+        assert_eq!(buf.next_token().unwrap().token_t, Comment);
+        assert_eq!(buf.next_token().unwrap().lexeme, "y");
+
+        let mut buf = KBuff::new("/* a /* b */");
+        assert_eq!(
+            buf.next_token(),
+            Err(LexError::UnterminatedComment(Span::new(0, 12, 1, 1)))
+        );
+        let mut buf = KBuff::new("/*/");
+        assert_eq!(
+            buf.next_token(),
+            Err(LexError::UnterminatedComment(Span::new(0, 3, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn test_parse_doc_comment() {
+        let mut buf = KBuff::new("/// Adds two numbers.\ndef");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(
+                DocComment,
+                "Adds two numbers.".to_owned(),
+                Span::new(0, 21, 1, 1)
+            )
+        );
+        assert_eq!(buf.next_token().unwrap().token_t, Def);
+
+        let mut buf = KBuff::new("/// Adds.\r\ndef");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok.lexeme, "Adds.");
+        assert_eq!(tok.span, Span::new(0, 10, 1, 1));
+    }
+
+    #[test]
+    fn test_parse_all_ops() {
+        let ops = "% && || & | ^ << >> += -= *= /= %= &= |= ^= <<= >>= <= >= == != ! < > = + - * /";
+        let lexemes: Vec<_> = KBuff::new(ops)
+            .tokenize()
+            .0
+            .unwrap()
+            .into_iter()
+            .map(|tok| {
+                assert_eq!(tok.token_t, Operator);
+                tok.lexeme
+            })
+            .collect();
+        assert_eq!(lexemes, ops.split(' ').collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parse_ops_maximal_munch() {
+        let mut buf = KBuff::new("a<<=b<=c->d");
+        assert_eq!(buf.next_token().unwrap().lexeme, "a");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "<<=".to_owned(), Span::new(1, 4, 1, 2))
+        );
+        assert_eq!(buf.next_token().unwrap().lexeme, "b");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Operator, "<=".to_owned(), Span::new(5, 7, 1, 6))
+        );
+        assert_eq!(buf.next_token().unwrap().lexeme, "c");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Arrow, "".to_owned(), Span::new(8, 10, 1, 9))
+        );
+        assert_eq!(buf.next_token().unwrap().lexeme, "d");
+
+        let mut buf = KBuff::new("x/=2/y");
+        buf.next_token().unwrap();
+        assert_eq!(buf.next_token().unwrap().lexeme, "/=");
+        buf.next_token().unwrap();
+        assert_eq!(buf.next_token().unwrap().lexeme, "/");
+        assert_eq!(buf.next_token().unwrap().lexeme, "y");
+    }
+
+    #[test]
+    fn test_parse_keywords() {
+        for (name, token_t) in KEYWORDS {
+            let tok = KBuff::new(name).next_token().unwrap();
+            assert_eq!(
+                tok,
+                Token::new(*token_t, "".to_owned(), Span::new(0, name.len(), 1, 1))
+            );
+        }
+
+        let mut buf = KBuff::new("iffy truex in_");
+        assert_eq!(buf.next_token().unwrap().token_t, Ident);
+        assert_eq!(buf.next_token().unwrap().token_t, Ident);
+        assert_eq!(buf.next_token().unwrap().token_t, Ident);
+    }
+
+    #[test]
+    fn test_lexemes_borrow_input() {
+        let input = "foo 12 \"plain\" \"esc\\n\" // note";
+        let tokens = KBuff::new(input).tokenize().0.unwrap();
+        let borrowed: Vec<_> = tokens
+            .iter()
+            .map(|tok| matches!(tok.lexeme, Cow::Borrowed(_)))
+            .collect();
+        assert_eq!(borrowed, vec![true, true, true, false, true]);
+        assert_eq!(tokens[3].lexeme, "esc\n");
+
+        let owned: Token<'static> = tokens[0].clone().into_owned();
+        assert_eq!(owned, tokens[0]);
+    }
+
+    #[test]
+    fn test_parse_unicode_idents() {
+        let mut buf = KBuff::new("_foo __ café π_2 x²");
+        let tok = buf.next_token().unwrap();
+        assert_eq!(
+            tok,
+            Token::new(Ident, "_foo".to_owned(), Span::new(0, 4, 1, 1))
+        );
+        assert_eq!(buf.next_token().unwrap().lexeme, "__");
+        assert_eq!(buf.next_token().unwrap().lexeme, "café");
+        assert_eq!(buf.next_token().unwrap().lexeme, "π_2");
+        assert_eq!(buf.next_token().unwrap().lexeme, "x");
+        assert_eq!(
+            buf.next_token(),
+            Err(LexError::UnexpectedChar('²', Span::new(20, 22, 1, 19)))
+        );
+
+        // Digits other than ASCII ones don't start numbers.
+        let mut buf = KBuff::new("²");
+        assert_eq!(
+            buf.next_token(),
+            Err(LexError::UnexpectedChar('²', Span::new(0, 2, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn test_idents_are_nfc() {
+        // `e` followed by a combining acute accent.
+        let decomposed = "cafe\u{301}";
+        let mut buf = KBuff::new(decomposed);
+        let tok = buf.next_token().unwrap();
+        assert_eq!(tok.lexeme, "café");
+        assert_eq!(tok.span, Span::new(0, 6, 1, 1));
+        assert_eq!(buf.next_token().unwrap().token_t, EOF);
+        assert!(buf.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_confusable_idents_warn() {
+        // Latin `a` and Cyrillic `а`.
+        let input = "paypal pаypal paypal";
+        let mut buf = KBuff::new(input);
+        while buf.next_token().unwrap().token_t != EOF {}
+        assert_eq!(KBuff::new(input).tokenize().1, buf.warnings());
+        assert_eq!(
+            buf.warnings(),
+            &[
+                LexWarning::MixedScript("pаypal".to_owned(), Span::new(7, 14, 1, 8)),
+                LexWarning::Confusable(
+                    "pаypal".to_owned(),
+                    "paypal".to_owned(),
+                    Span::new(7, 14, 1, 8)
+                ),
+            ]
+        );
+    }
+}
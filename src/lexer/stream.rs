@@ -0,0 +1,344 @@
+use super::ident::Confusables;
+use super::{KBuff, LexError, LexWarning, Span, Token, TokenStream, TokenType};
+use std::io::{BufRead, ErrorKind};
+
+// Lexes from any `BufRead`, reading only as much input as the next token needs.
+pub struct KStream<R> {
+    reader: R,
+    // Decoded input, lexed up to `cursor`. Only compacted when more is read.
+    pending: String,
+    cursor: usize,
+    // Bytes of a UTF-8 sequence split across two reads.
+    partial: Vec<u8>,
+    base: Span,
+    eof: bool,
+    // I/O and encoding errors found while reading, reported in input order.
+    errors: Vec<LexError>,
+    warnings: Vec<LexWarning>,
+    confusables: Confusables,
+}
+
+impl<R: BufRead> KStream<R> {
+    pub fn new(reader: R) -> Self {
+        KStream {
+            reader,
+            pending: String::new(),
+            cursor: 0,
+            partial: Vec::new(),
+            base: Span::new(0, 0, 1, 1),
+            eof: false,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            confusables: Confusables::default(),
+        }
+    }
+
+    pub fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+
+    // Lex the whole input, reporting every lexical error rather than just the first,
+    // along with any warnings.
+    pub fn tokenize(mut self) -> (Result<Vec<Token<'static>>, Vec<LexError>>, Vec<LexWarning>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+        let result = if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        };
+        (result, self.warnings)
+    }
+
+    pub fn next_token(&mut self) -> Result<Token<'static>, LexError> {
+        loop {
+            let rest = &self.pending[self.cursor..];
+            let mut buff = KBuff::new(rest);
+            let result = buff.next_token();
+            let consumed = buff.offset;
+
+            // A token running up to the end of what has been read may continue in the next read,
+            // and one decided by looking past that end may lex differently once more is read.
+            if (consumed == rest.len() || buff.looked_past_end) && !self.eof {
+                self.fill();
+                continue;
+            }
+
+            // Report reading errors in input order, before the token that follows them.
+            let start = match &result {
+                Ok(token) => token.span.start,
+                Err(err) => err.span().start,
+            };
+            if let Some(err) = self.errors.first() {
+                if err.span().start <= start + self.base.start {
+                    return Err(self.errors.remove(0));
+                }
+            }
+
+            let result = match result {
+                Ok(mut token) => {
+                    token.span = self.shift(token.span);
+                    if token.token_t == TokenType::Ident {
+                        self.confusables
+                            .check(&token.lexeme, token.span, &mut self.warnings);
+                    }
+                    Ok(token.into_owned())
+                }
+                Err(mut err) => {
+                    *err.span_mut() = self.shift(err.span());
+                    Err(err)
+                }
+            };
+            self.advance(consumed);
+            return result;
+        }
+    }
+
+    // Read at least as much again as is left to lex, so long tokens are not re-lexed once per read.
+    fn fill(&mut self) {
+        // Drop lexed input once it is most of the buffer, so each byte is moved a bounded
+        // number of times however large the reads are.
+        if self.cursor > self.pending.len() / 2 {
+            self.pending.drain(..self.cursor);
+            self.cursor = 0;
+        }
+        let wanted = (self.pending.len() - self.cursor).max(1);
+        let mut read = 0;
+        while read < wanted {
+            let bytes = match self.reader.fill_buf() {
+                Ok([]) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(bytes) => bytes.to_vec(),
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    let at = self.end();
+                    self.errors.push(LexError::Io(err.to_string(), at));
+                    self.eof = true;
+                    break;
+                }
+            };
+            self.reader.consume(bytes.len());
+            read += bytes.len();
+            self.partial.extend_from_slice(&bytes);
+            self.decode();
+        }
+
+        if self.eof && !self.partial.is_empty() {
+            let at = self.end();
+            self.errors.push(LexError::InvalidUtf8(at));
+            self.partial.clear();
+        }
+    }
+
+    // Move every complete UTF-8 sequence from `partial` into `pending`.
+    fn decode(&mut self) {
+        loop {
+            match std::str::from_utf8(&self.partial) {
+                Ok(text) => {
+                    self.pending.push_str(text);
+                    self.partial.clear();
+                    return;
+                }
+                Err(err) => {
+                    let valid = err.valid_up_to();
+                    let text = std::str::from_utf8(&self.partial[..valid]).unwrap();
+                    self.pending.push_str(text);
+                    match err.error_len() {
+                        // Incomplete sequence at the end, wait for the rest of it.
+                        None => {
+                            self.partial.drain(..valid);
+                            return;
+                        }
+                        // Blank out invalid bytes so later offsets still match the input.
+                        Some(len) => {
+                            let at = self.end();
+                            self.errors.push(LexError::InvalidUtf8(at));
+                            self.pending.push_str(&" ".repeat(len));
+                            self.partial.drain(..valid + len);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Move `cursor` past `len` lexed bytes, keeping `base` at its position in the input.
+    fn advance(&mut self, len: usize) {
+        for c in self.pending[self.cursor..self.cursor + len].chars() {
+            if c == '\n' {
+                self.base.line += 1;
+                self.base.col = 1;
+            } else {
+                self.base.col += 1;
+            }
+        }
+        self.base.start += len;
+        self.base.end = self.base.start;
+        self.cursor += len;
+    }
+
+    // Empty span at the end of what has been decoded so far.
+    fn end(&self) -> Span {
+        let mut end = self.base;
+        for c in self.pending[self.cursor..].chars() {
+            if c == '\n' {
+                end.line += 1;
+                end.col = 1;
+            } else {
+                end.col += 1;
+            }
+        }
+        end.start += self.pending.len() - self.cursor;
+        end.end = end.start;
+        end
+    }
+
+    // Translate a span relative to `cursor` into one relative to the whole input.
+    fn shift(&self, span: Span) -> Span {
+        Span {
+            start: span.start + self.base.start,
+            end: span.end + self.base.start,
+            line: span.line + self.base.line - 1,
+            col: match span.line {
+                1 => span.col + self.base.col - 1,
+                _ => span.col,
+            },
+        }
+    }
+}
+
+impl<'a, R: BufRead> TokenStream<'a> for KStream<R> {
+    fn next_token(&mut self) -> Result<Token<'a>, LexError> {
+        KStream::next_token(self)
+    }
+
+    fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+}
+
+impl<R: BufRead> Iterator for KStream<R> {
+    type Item = Result<Token<'static>, LexError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(Token {
+                token_t: TokenType::EOF,
+                ..
+            }) => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, BufReader, Read};
+
+    // Hands out its input a few bytes at a time, like a slow pipe.
+    struct Trickle<'a> {
+        input: &'a [u8],
+        chunk: usize,
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.chunk.min(buf.len()).min(self.input.len());
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input = &self.input[len..];
+            Ok(len)
+        }
+    }
+
+    fn trickle(input: &str, chunk: usize) -> BufReader<Trickle<'_>> {
+        BufReader::with_capacity(
+            chunk,
+            Trickle {
+                input: input.as_bytes(),
+                chunk,
+            },
+        )
+    }
+
+    #[test]
+    fn test_stream_matches_kbuff() {
+        let input = "/// doc\ndef fooé(x, y) x <<= 0x1F + \"héllo\\n\" /* a\n /* b */ */\n\
+            extern 1.5e-3 2.e5 r##\"a \"# b\"## r\"c\" 144.sqrt() a->b 0b10 //// plain\n/// end";
+        let expected = KBuff::new(input).tokenize().0.unwrap();
+        for chunk in 1..12 {
+            let tokens = KStream::new(trickle(input, chunk)).tokenize().0.unwrap();
+            assert_eq!(tokens, expected, "chunk size {}", chunk);
+        }
+    }
+
+    #[test]
+    fn test_stream_large_reads() {
+        // Lexing stays linear when a single read holds many tokens.
+        let input = "def f(x, y) x * 0x1F + \"s\" // c\n".repeat(20_000);
+        let expected = KBuff::new(&input).tokenize().0.unwrap();
+        let reader = BufReader::with_capacity(1 << 20, input.as_bytes());
+        let tokens = KStream::new(reader).tokenize().0.unwrap();
+        assert_eq!(tokens.len(), expected.len());
+        assert_eq!(tokens.last(), expected.last());
+    }
+
+    #[test]
+    fn test_stream_warnings() {
+        let input = "paypal x pаypal";
+        let mut buff = KBuff::new(input);
+        while buff.next_token().unwrap().token_t != TokenType::EOF {}
+        let mut stream = KStream::new(trickle(input, 3));
+        while stream.next_token().unwrap().token_t != TokenType::EOF {}
+        assert_eq!(stream.warnings(), buff.warnings());
+        assert_eq!(stream.warnings().len(), 2);
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let input = "x $ \"open";
+        let expected = KBuff::new(input).tokenize().0.unwrap_err();
+        let errors = KStream::new(trickle(input, 2)).tokenize().0.unwrap_err();
+        assert_eq!(errors, expected);
+    }
+
+    #[test]
+    fn test_stream_invalid_utf8() {
+        let input: &[u8] = b"ab \xff cd";
+        let mut stream = KStream::new(input);
+        assert_eq!(stream.next_token().unwrap().lexeme, "ab");
+        assert_eq!(
+            stream.next_token(),
+            Err(LexError::InvalidUtf8(Span::new(3, 3, 1, 4)))
+        );
+        assert_eq!(stream.next_token().unwrap().lexeme, "cd");
+        assert_eq!(stream.next_token().unwrap().token_t, TokenType::EOF);
+    }
+
+    #[test]
+    fn test_stream_io_error() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("pipe closed"))
+            }
+        }
+
+        let mut stream = KStream::new(BufReader::new(Broken));
+        assert_eq!(
+            stream.next_token(),
+            Err(LexError::Io(
+                "pipe closed".to_owned(),
+                Span::new(0, 0, 1, 1)
+            ))
+        );
+        assert_eq!(stream.next_token().unwrap().token_t, TokenType::EOF);
+    }
+}
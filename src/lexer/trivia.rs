@@ -0,0 +1,228 @@
+use super::{KBuff, LexError, LexWarning, Span, Token, TokenType};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    Comment,
+    DocComment,
+    // Input the lexer rejected, reported separately as a `LexError`.
+    Skipped,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+// A token together with the source text around it. Trailing trivia runs up to and
+// including the end of the token's line, everything else leads the next token.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LosslessToken<'a> {
+    pub token: Token<'a>,
+    // The token exactly as written, Ex: a string with its quotes and escapes.
+    pub text: &'a str,
+    pub leading: Vec<Trivia<'a>>,
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl<'a> Display for LosslessToken<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            write!(f, "{}", trivia.text)?;
+        }
+        write!(f, "{}", self.text)?;
+        for trivia in &self.trailing {
+            write!(f, "{}", trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> KBuff<'a> {
+    // Lex so that every byte of the input belongs to a token or its trivia. The last
+    // token is always `EOF`, holding whatever trivia ends the input.
+    pub fn tokenize_lossless(mut self) -> (Vec<LosslessToken<'a>>, Vec<LexError>, Vec<LexWarning>) {
+        let input = self.input;
+        let mut tokens: Vec<LosslessToken<'a>> = Vec::new();
+        let mut errors = Vec::new();
+        let mut trivia = Vec::new();
+        // Everything before `pos` has been given to a token or to `trivia`.
+        let mut pos = 0;
+        let mut line = (1, 1);
+
+        loop {
+            let token = match self.next_token() {
+                Ok(token) => token,
+                Err(err) => {
+                    // Skipped input, after any whitespace that came before it.
+                    let text = &input[pos..self.offset];
+                    let skipped = pos + text.len() - text.trim_start().len();
+                    line = split_whitespace(input, pos, skipped, line, &mut trivia);
+                    line = push_trivia(
+                        input,
+                        TriviaKind::Skipped,
+                        skipped,
+                        self.offset,
+                        line,
+                        &mut trivia,
+                    );
+                    pos = self.offset;
+                    errors.push(err);
+                    continue;
+                }
+            };
+
+            let Span { start, end, .. } = token.span;
+            line = split_whitespace(input, pos, start, line, &mut trivia);
+            pos = end;
+            let kind = match token.token_t {
+                TokenType::Comment => TriviaKind::Comment,
+                TokenType::DocComment => TriviaKind::DocComment,
+                _ => {
+                    line = advance(&input[start..end], line);
+                    let is_eof = token.token_t == TokenType::EOF;
+                    if let Some(prev) = tokens.last_mut() {
+                        let eol = trivia
+                            .iter()
+                            .position(|t: &Trivia| t.kind == TriviaKind::Newline)
+                            .map_or(trivia.len(), |i| i + 1);
+                        prev.trailing = trivia.drain(..eol).collect();
+                    }
+                    tokens.push(LosslessToken {
+                        token,
+                        text: &input[start..end],
+                        leading: std::mem::take(&mut trivia),
+                        trailing: Vec::new(),
+                    });
+                    if is_eof {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            line = push_trivia(input, kind, start, end, line, &mut trivia);
+        }
+        (tokens, errors, self.warnings)
+    }
+}
+
+// Split the text between two tokens into runs of blanks and single newlines.
+fn split_whitespace<'a>(
+    input: &'a str,
+    mut pos: usize,
+    end: usize,
+    mut line: (usize, usize),
+    trivia: &mut Vec<Trivia<'a>>,
+) -> (usize, usize) {
+    while pos < end {
+        let rest = &input[pos..end];
+        let (kind, len) = if rest.starts_with('\n') {
+            (TriviaKind::Newline, 1)
+        } else if rest.starts_with("\r\n") {
+            (TriviaKind::Newline, 2)
+        } else if rest.starts_with(char::is_whitespace) {
+            let len = rest
+                .find(|c: char| !c.is_whitespace() || c == '\n' || c == '\r')
+                .unwrap_or(rest.len());
+            // A lone `\r` is just whitespace.
+            (TriviaKind::Whitespace, len.max(1))
+        } else {
+            // Consumed by the lexer but outside any token.
+            let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            (TriviaKind::Skipped, len)
+        };
+        line = push_trivia(input, kind, pos, pos + len, line, trivia);
+        pos += len;
+    }
+    line
+}
+
+fn push_trivia<'a>(
+    input: &'a str,
+    kind: TriviaKind,
+    start: usize,
+    end: usize,
+    line: (usize, usize),
+    trivia: &mut Vec<Trivia<'a>>,
+) -> (usize, usize) {
+    let text = &input[start..end];
+    trivia.push(Trivia {
+        kind,
+        text,
+        span: Span::new(start, end, line.0, line.1),
+    });
+    advance(text, line)
+}
+
+// Line and column just after `text`, given where it starts.
+fn advance(text: &str, (mut line, mut col): (usize, usize)) -> (usize, usize) {
+    for c in text.chars() {
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (line, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TriviaKind::*;
+
+    fn round_trip(input: &str) -> String {
+        let (tokens, ..) = KBuff::new(input).tokenize_lossless();
+        tokens.iter().map(|token| token.to_string()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let inputs = [
+            "",
+            "   \n\t ",
+            "def foo(x, y) x + y",
+            "/// Adds.\r\ndef add(a, b) // sum\n  a + b; /* nested /* c */ */\n\n",
+            "extern puts(s)\n\"a \\n \\u{e9} é\" r#\"raw\"# 0x1F_FF 144.sqrt()",
+            "x $ 1k0 \"bad \\q escape\" @\n\"unterminated",
+            "a \r b\0 after nul",
+        ];
+        for input in inputs.iter() {
+            assert_eq!(&round_trip(input), input);
+        }
+    }
+
+    #[test]
+    fn test_trivia_attachment() {
+        let (tokens, errors, _) = KBuff::new("a // one\n\n  /// doc\nb $ c").tokenize_lossless();
+        assert_eq!(
+            errors,
+            vec![LexError::UnexpectedChar('$', Span::new(22, 23, 4, 3))]
+        );
+
+        let kinds = |trivia: &[Trivia]| trivia.iter().map(|t| t.kind).collect::<Vec<_>>();
+        let [a, b, c, eof] = &tokens[..] else {
+            panic!("Expected 4 tokens found {:?}", tokens)
+        };
+        assert_eq!(a.text, "a");
+        assert!(a.leading.is_empty());
+        assert_eq!(kinds(&a.trailing), vec![Whitespace, Comment, Newline]);
+
+        assert_eq!(b.text, "b");
+        assert_eq!(
+            kinds(&b.leading),
+            vec![Newline, Whitespace, DocComment, Newline]
+        );
+        assert_eq!(b.leading[2].text, "/// doc");
+        assert_eq!(b.leading[2].span, Span::new(12, 19, 3, 3));
+        assert_eq!(kinds(&b.trailing), vec![Whitespace, Skipped, Whitespace]);
+
+        assert_eq!(c.token.lexeme, "c");
+        assert_eq!(eof.token.token_t, TokenType::EOF);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod lexer;
+pub mod parser;
+pub mod resolve;
+pub mod symbol;
@@ -2,12 +2,24 @@
 // #[cfg(test)]
 // extern crate uuid;
 
-pub mod lexer;
-pub mod parser;
+use k_lang::{lexer, parser, resolve};
 
 fn main() {
     let lexer = lexer::KBuff::new("def foo(x, y) x + y");
-    let _parsed = parser::Parser::new(4, lexer);
+    let mut parser = parser::Parser::new(lexer);
+    let mut parsed = parser::parse(&mut parser);
 
-    // dbg!(parsed);
+    for err in parser.lex_errors() {
+        eprintln!("{}", err);
+    }
+    for warning in parser.lex_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    for err in parser.errors() {
+        eprintln!("{}", err);
+    }
+    for err in resolve::resolve(&mut parsed) {
+        eprintln!("{}", err);
+    }
+    println!("{:#?}", parsed);
 }
@@ -1,21 +1,63 @@
-use crate::lexer::Token;
+use crate::lexer::{Number, Span};
+use crate::symbol::Symbol;
+use AST::*;
 
 #[derive(Debug, PartialEq)]
 pub enum AST {
-    ExternNode(ProtoType),
+    // The span runs from `extern` to the end of the prototype.
+    ExternNode(ProtoType, Span),
     FunctionNode(Function),
     Expr(Expression),
 }
 
+impl AST {
+    pub fn span(&self) -> Span {
+        match self {
+            ExternNode(_, span) => *span,
+            FunctionNode(function) => function.span,
+            Expr(expr) => expr.span,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct ProtoType {
-    pub func_name: Token,
-    pub args: Vec<Token>,
+    pub func_name: Symbol,
+    pub args: Vec<Symbol>,
+    // Annotation of each argument in `args`, if it has one.
+    pub arg_types: Vec<Option<Type>>,
+    pub ret_type: Option<Type>,
+    // Text of the `///` comments written before the definition.
+    pub doc: Option<String>,
+    // From the name to the return type or closing parenthesis.
+    pub span: Span,
 }
 
 impl ProtoType {
-    pub fn new(func_name: Token, args: Vec<Token>) -> Self {
-        ProtoType { func_name, args }
+    pub fn new(func_name: Symbol, args: Vec<Symbol>, span: Span) -> Self {
+        ProtoType {
+            func_name,
+            arg_types: vec![None; args.len()],
+            args,
+            ret_type: None,
+            doc: None,
+            span,
+        }
+    }
+}
+
+// A type as written in an annotation. Names are only checked when they are resolved.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Type {
+    Named(Symbol, Span),
+    Array(Box<Type>, Span),
+}
+
+impl Type {
+    pub fn span(&self) -> Span {
+        match self {
+            Type::Named(_, span) | Type::Array(_, span) => *span,
+        }
     }
 }
 
@@ -23,22 +65,71 @@ impl ProtoType {
 pub struct Function {
     pub prototype: ProtoType,
     pub body: Expression,
+    pub span: Span,
 }
 
 impl Function {
-    pub fn new(prototype: ProtoType, body: Expression) -> Self {
-        Function { prototype, body }
+    pub fn new(prototype: ProtoType, body: Expression, span: Span) -> Self {
+        Function {
+            prototype,
+            body,
+            span,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Literal {
+    Number(Number),
+    Str(String),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Expression {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expression { kind, span }
     }
 }
 
 #[derive(PartialEq, Clone, Debug)]
-pub enum Expression {
-    LiteralEpxr(Token),
+pub enum ExprKind {
+    LiteralEpxr(Literal),
     BoolEpxr(bool),
-    VariableExpr(Token),
+    VariableExpr(Symbol),
     BinaryExpr(String, Box<Expression>, Box<Expression>),
     UnaryExpr(String, Box<Expression>),
-    CallExpr(String, Vec<Expression>),
+    // Callee and arguments, the callee is any expression giving a function.
+    CallExpr(Box<Expression>, Vec<Expression>),
+    // Condition, then branch and else branch. `else if` nests in the else branch.
+    IfExpr(Box<Expression>, Box<Expression>, Box<Expression>),
+    // Loop variable, its start value, the condition to keep going, the optional step and
+    // the body. The variable is only in scope in the condition, step and body.
+    ForExpr(
+        Symbol,
+        Box<Expression>,
+        Box<Expression>,
+        Option<Box<Expression>>,
+        Box<Expression>,
+    ),
+    // `var` bindings with their optional initial values, and the body they are in scope for.
+    VarExpr(Vec<(Symbol, Option<Expression>)>, Box<Expression>),
+    // Target and value, the target is only checked when names are resolved.
+    AssignExpr(Box<Expression>, Box<Expression>),
+    // Expressions run for their effects, then the one giving the block its value. A block
+    // ending in `;` has no value expression, and evaluates to unit.
+    Block(Vec<Expression>, Option<Box<Expression>>),
+    ArrayExpr(Vec<Expression>),
+    // Array and index. Assigning to it sets the element.
+    IndexExpr(Box<Expression>, Box<Expression>),
+    // The `len` builtin, the number of elements in an array.
+    LenExpr(Box<Expression>),
+    // Parameters and body of an anonymous function, which can use the variables around it.
+    LambdaExpr(Vec<Symbol>, Box<Expression>),
 }
 
 // pub enum PartParsingResult<T> {
@@ -1,222 +1,1463 @@
 pub mod ast;
-use super::lexer::{KBuff, Token, TokenType, TokenType::*};
-use ast::{Expression, Expression::*, ProtoType, AST, AST::*};
+use super::lexer::{LexError, LexWarning, Span, Token, TokenStream, TokenType, TokenType::*};
+use crate::symbol::Symbol;
+use ast::{ExprKind::*, Expression, Function, Literal, ProtoType, Type, AST, AST::*};
 
-use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
 
 pub struct Parser<'a> {
-    k: usize,
+    // Tokens pulled from the lexer. Those before `pos` are consumed, and only kept while
+    // a mark may still rewind to them.
+    look_ahead: VecDeque<Token<'a>>,
     pos: usize,
-    look_ahead: Vec<Token>,
-    lexer: RefCell<KBuff<'a>>,
+    // Consumed tokens already dropped from the front of `look_ahead`.
+    dropped: usize,
+    marks: usize,
+    // Brackets of the current item consumed and not yet closed, so recovery skips
+    // whole blocks rather than stopping at a `;` inside one.
+    nesting: usize,
+    lexer: Box<dyn TokenStream<'a> + 'a>,
+    lex_errors: Vec<LexError>,
+    errors: Vec<ParseError>,
+    // Doc comments keyed by the start offset of the token that follows them.
+    docs: HashMap<usize, std::string::String>,
+}
+
+// A position in the token stream to rewind to. Not `Copy`, so each mark is given back
+// exactly once.
+#[derive(Debug, PartialEq)]
+pub struct Mark {
+    offset: usize,
+    // Marks still held when this one was taken.
+    depth: usize,
+    nesting: usize,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(k: usize, lexer: KBuff<'a>) -> Self {
+    pub fn new(lexer: impl TokenStream<'a> + 'a) -> Self {
         Parser {
-            k,
+            look_ahead: VecDeque::new(),
             pos: 0,
-            look_ahead: Vec::new(),
-            lexer: RefCell::new(lexer),
+            dropped: 0,
+            marks: 0,
+            nesting: 0,
+            lexer: Box::new(lexer),
+            lex_errors: Vec::new(),
+            errors: Vec::new(),
+            docs: HashMap::new(),
         }
     }
 
-    pub fn fill_look_ahead(&mut self) {
-        self.look_ahead.append(
-            &mut vec![0; self.k]
-                .into_iter()
-                .map(|_| self.lexer.borrow_mut().next_token())
-                .collect::<Vec<Token>>(),
-        );
+    // Lexical errors skipped over while filling the look ahead.
+    pub fn lex_errors(&self) -> &[LexError] {
+        &self.lex_errors
+    }
+
+    // Confusable and mixed-script identifiers the lexer warned about.
+    pub fn lex_warnings(&self) -> &[LexWarning] {
+        self.lexer.warnings()
+    }
+
+    // Syntax errors recovered from by skipping to the next item.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    // The `n`th token not yet consumed, starting at 1. Past the end of input this is `EOF`.
+    pub fn peek(&mut self, n: usize) -> &Token<'a> {
+        assert!(n >= 1, "tokens are peeked from 1");
+        while self.look_ahead.len() < self.pos + n {
+            let token = match self.look_ahead.back() {
+                Some(eof @ Token { token_t: EOF, .. }) => eof.clone(),
+                _ => self.lex(),
+            };
+            self.look_ahead.push_back(token);
+        }
+        &self.look_ahead[self.pos + n - 1]
+    }
+
+    // Remember the current position so a speculative parse can be undone. Every mark
+    // must be given back to `rewind` or `release`, innermost first.
+    pub fn mark(&mut self) -> Mark {
+        let mark = Mark {
+            offset: self.dropped + self.pos,
+            depth: self.marks,
+            nesting: self.nesting,
+        };
+        self.marks += 1;
+        mark
+    }
+
+    // Go back to `mark`, so the tokens consumed since are read again.
+    pub fn rewind(&mut self, mark: Mark) {
+        self.pos = mark.offset - self.dropped;
+        self.nesting = mark.nesting;
+        self.release(mark);
+    }
+
+    // Keep everything consumed since `mark`.
+    pub fn release(&mut self, mark: Mark) {
+        debug_assert_eq!(mark.depth + 1, self.marks, "marks released out of order");
+        self.marks -= 1;
+        if self.marks == 0 {
+            self.look_ahead.drain(..self.pos);
+            self.dropped += self.pos;
+            self.pos = 0;
+        }
+    }
+
+    // Pull the next valid token from the lexer, recording any errors in between.
+    // Comments are skipped, doc comments are kept aside when they precede a `def` or `extern`.
+    fn lex(&mut self) -> Token<'a> {
+        let mut doc = Vec::new();
+        loop {
+            match self.lexer.next_token() {
+                Ok(Token {
+                    token_t: Comment, ..
+                }) => {}
+                Ok(Token {
+                    token_t: DocComment,
+                    lexeme,
+                    ..
+                }) => doc.push(lexeme),
+                Ok(token) => {
+                    if !doc.is_empty() && matches!(token.token_t, Def | Extern) {
+                        self.docs.insert(token.span.start, doc.join("\n"));
+                    }
+                    return token;
+                }
+                Err(err) => self.lex_errors.push(err),
+            }
+        }
     }
 
-    // TODO:
-    // fn extend_look_ahead(&mut self) {
-    //     vec![0; self.look_ahead.len()] self.lexer
-    //         .borrow_mut()
-    //         .next_token()
-    //         .take(self.look_ahead.len());
-    // }
+    // Doc comment written directly before the next token.
+    fn take_doc(&mut self) -> Option<std::string::String> {
+        let start = self.peek(1).span.start;
+        self.docs.remove(&start)
+    }
 
-    fn next_token(&self, i: usize) -> &TokenType {
-        // TODO:
-        // if i >= self.look_ahead.len() {
-        //     self.extend_look_ahead();
-        // }
-        &self.look_ahead[(self.pos + i - 1) % self.k].token_t
+    fn next_token(&mut self, i: usize) -> TokenType {
+        self.peek(i).token_t
     }
 
-    fn token(&mut self, i: usize) -> Token {
-        // if i >= self.look_ahead.len() {
-        //     self.extend_look_ahead();
-        // }
-        let res = self.look_ahead[(self.pos + i - 1) % self.k].clone();
+    fn lexeme(&mut self, i: usize) -> &str {
+        &self.peek(i).lexeme
+    }
+
+    fn token(&mut self, i: usize) -> Token<'a> {
+        let res = self.peek(i).clone();
         self.consume();
         res
     }
 
     fn consume(&mut self) {
-        self.look_ahead[self.pos] = self.lexer.borrow_mut().next_token();
-        self.pos = (self.pos + 1) % self.k;
+        match self.next_token(1) {
+            LParenthesis | LBracket | LBrace => self.nesting += 1,
+            RParenthesis | RBracket | RBrace => self.nesting = self.nesting.saturating_sub(1),
+            _ => {}
+        }
+        if self.marks == 0 {
+            self.look_ahead.pop_front();
+            self.dropped += 1;
+        } else {
+            self.pos += 1;
+        }
+    }
+
+    // Error for the next token not being one of `expected`, without consuming it.
+    fn error(&mut self, expected: &[TokenType]) -> ParseError {
+        let found = self.peek(1).clone().into_owned();
+        ParseError::new(expected.to_vec(), found)
+    }
+
+    fn expect(&mut self, expected: TokenType) -> Result<Token<'a>, ParseError> {
+        if self.next_token(1) == expected {
+            Ok(self.token(1))
+        } else {
+            Err(self.error(&[expected]))
+        }
+    }
+
+    fn expect_op(&mut self, op: &'static str) -> Result<Token<'a>, ParseError> {
+        if self.next_token(1) == Operator && self.lexeme(1) == op {
+            Ok(self.token(1))
+        } else {
+            Err(self.error_op(&[Operator], op))
+        }
+    }
+
+    // Like `error`, where the `Operator` expected is `op` in particular.
+    fn error_op(&mut self, expected: &[TokenType], op: &'static str) -> ParseError {
+        let mut err = self.error(expected);
+        err.expected_op = Some(op);
+        err
+    }
+
+    // Skip the rest of a broken item, up to where the next one can start. A `;` only ends
+    // the item outside brackets, `def` and `extern` can't appear inside one.
+    fn synchronize(&mut self) {
+        loop {
+            match self.next_token(1) {
+                Def | Extern | EOF => return,
+                Delimiter if self.nesting == 0 => return,
+                _ => self.consume(),
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub expected: Vec<TokenType>,
+    // Which operator was wanted, when `expected` names a specific one.
+    pub expected_op: Option<&'static str>,
+    pub found: Token<'static>,
+}
+
+impl ParseError {
+    pub fn new(expected: Vec<TokenType>, found: Token<'static>) -> Self {
+        ParseError {
+            expected,
+            expected_op: None,
+            found,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.found.span
     }
 }
 
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: expected ", self.span())?;
+        if self.expected.len() > 1 {
+            write!(f, "one of ")?;
+        }
+        for (i, token_t) in self.expected.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match (token_t, self.expected_op) {
+                (Operator, Some(op)) => write!(f, "{:?}", op)?,
+                _ => write!(f, "{:?}", token_t)?,
+            }
+        }
+        match self.found.token_t {
+            EOF => write!(f, " found end of input"),
+            // Punctuation tokens carry no lexeme.
+            _ if self.found.lexeme.is_empty() => write!(f, " found {:?}", self.found.token_t),
+            _ => write!(f, " found {:?}", self.found.lexeme),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Tokens an expression can start with.
+const EXPR_START: &[TokenType] = &[
+    Ident,
+    Numeric,
+    String,
+    True,
+    False,
+    LParenthesis,
+    If,
+    For,
+    Var,
+    LBrace,
+    LBracket,
+    Operator,
+];
+
+// program : [[statement | expression] Delimiter ?]*;
 pub fn parse(parser: &mut Parser) -> Vec<AST> {
-    parser.fill_look_ahead();
     let mut ast = Vec::new();
     loop {
-        match (parser.next_token(1), parser.next_token(2)) {
-            _ => {}
-        }
-
-        match parser.next_token(1) {
-            Extern => ast.push(parse_extern(parser)),
-            // Def => ast.push(parse_def(parser)),
-            _ => break,
+        parser.nesting = 0;
+        let item = match parser.next_token(1) {
+            Def => parse_def(parser),
+            Extern => parse_extern(parser),
+            Delimiter => {
+                parser.consume();
+                continue;
+            }
+            EOF => break,
+            _ => parse_expr(parser).map(Expr),
+        };
+        match item {
+            Ok(item) => ast.push(item),
+            Err(err) => {
+                parser.errors.push(err);
+                parser.synchronize();
+            }
         }
     }
     ast
 }
 
-// fn parse_def(parser: &mut Parser) -> AST {
-//     parser.consume();
-//     let prototype = parse_prototype(parser);
+fn parse_def(parser: &mut Parser) -> Result<AST, ParseError> {
+    let doc = parser.take_doc();
+    let def = parser.token(1);
+    let mut prototype = parse_prototype(parser)?;
+    prototype.doc = doc;
+    let body = parse_expr(parser)?;
 
-//     // let expression = parse_expr(parser);
+    let span = def.span.to(body.span);
+    Ok(FunctionNode(Function::new(prototype, body, span)))
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+// Binary operators with their binding power, higher binds tighter. Prefix `!` and `-`
+// bind tighter than any of them.
+pub const BINARY_OPS: &[(&str, u8, Assoc)] = &[
+    ("=", 1, Assoc::Right),
+    ("||", 2, Assoc::Left),
+    ("&&", 3, Assoc::Left),
+    ("==", 4, Assoc::Left),
+    ("!=", 4, Assoc::Left),
+    ("<", 4, Assoc::Left),
+    (">", 4, Assoc::Left),
+    ("<=", 4, Assoc::Left),
+    (">=", 4, Assoc::Left),
+    ("|", 5, Assoc::Left),
+    ("^", 6, Assoc::Left),
+    ("&", 7, Assoc::Left),
+    ("<<", 8, Assoc::Left),
+    (">>", 8, Assoc::Left),
+    ("+", 9, Assoc::Left),
+    ("-", 9, Assoc::Left),
+    ("*", 10, Assoc::Left),
+    ("/", 10, Assoc::Left),
+    ("%", 10, Assoc::Left),
+];
+
+pub const UNARY_OPS: &[&str] = &["!", "-"];
+
+fn binary_op(op: &str) -> Option<(u8, Assoc)> {
+    BINARY_OPS
+        .iter()
+        .find(|(name, ..)| *name == op)
+        .map(|&(_, prec, assoc)| (prec, assoc))
+}
 
-//     ExternNode(prototype)
-// }
+pub fn parse_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
+    parse_binary_expr(parser, 0)
+}
 
-pub fn parse_expr(parser: &mut Parser) -> AST {
+fn parse_unary_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
     match parser.next_token(1) {
-        Ident => Expr(parse_binary_expr(parser)),
-        Operator => Expr(parse_unary_expr(parser)),
-        _ => panic!("Expected Ident or Operator found {:?}", parser.token(1)),
+        Operator if UNARY_OPS.contains(&parser.lexeme(1)) => {
+            let op = parser.token(1);
+            let operand = parse_unary_expr(parser)?;
+            let span = op.span.to(operand.span);
+            Ok(Expression::new(
+                UnaryExpr(op.lexeme.into_owned(), Box::new(operand)),
+                span,
+            ))
+        }
+        _ => parse_postfix_expr(parser),
     }
 }
 
-fn parse_unary_expr(parser: &mut Parser) -> Expression {
+// postfix_expr : primary_expr [call | index | method_call]*;
+fn parse_postfix_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let mut expr = parse_primary(parser)?;
+    loop {
+        let start = expr.span;
+        let (kind, close) = match parser.next_token(1) {
+            LParenthesis => {
+                parser.consume();
+                let (args, close) = parse_list(parser, RParenthesis)?;
+                (CallExpr(Box::new(expr), args), close)
+            }
+            LBracket => {
+                parser.consume();
+                let index = parse_expr(parser)?;
+                let close = parser.expect(RBracket)?;
+                (IndexExpr(Box::new(expr), Box::new(index)), close)
+            }
+            // `recv.name(args)` is sugar for `name(recv, args)`.
+            Dot => {
+                parser.consume();
+                let name = parser.expect(Ident)?;
+                let callee = Expression::new(VariableExpr(Symbol::intern(&name.lexeme)), name.span);
+                parser.expect(LParenthesis)?;
+                let (args, close) = parse_list(parser, RParenthesis)?;
+                let args = std::iter::once(expr).chain(args).collect();
+                (CallExpr(Box::new(callee), args), close)
+            }
+            _ => return Ok(expr),
+        };
+        expr = Expression::new(kind, start.to(close.span));
+    }
+}
+
+fn parse_primary(parser: &mut Parser) -> Result<Expression, ParseError> {
     match (parser.next_token(1), parser.next_token(2)) {
-        (Ident, _) => return parse_primary(parser),
-        (Operator, Ident) => {
-            let op = parser.token(1);
-            UnaryExpr(op.lexeme, Box::new(parse_primary(parser)))
+        (Operator, _) if matches!(parser.lexeme(1), "|" | "||") => {
+            return parse_lambda_expr(parser)
+        }
+        (If, _) => return parse_if_expr(parser),
+        (For, _) => return parse_for_expr(parser),
+        (Var, _) => return parse_var_expr(parser),
+        (LBrace, _) => return parse_block(parser),
+        (LBracket, _) => {
+            let open = parser.token(1);
+            let (elements, close) = parse_list(parser, RBracket)?;
+            let span = open.span.to(close.span);
+            return Ok(Expression::new(ArrayExpr(elements), span));
         }
-        _ => panic!(
-            "Expect Ident , _ or Operator, Ident found : {:?} and {:?}",
-            parser.token(1),
-            parser.token(1)
-        ),
+        (LParenthesis, _) => {
+            let open = parser.token(1);
+            let mut expr = parse_expr(parser)?;
+            let close = parser.expect(RParenthesis)?;
+            // The parentheses belong to the grouped expression.
+            expr.span = open.span.to(close.span);
+            return Ok(expr);
+        }
+        (Numeric | String | Ident | True | False, _) => {}
+        _ => return Err(parser.error(EXPR_START)),
     }
+
+    let token = parser.token(1);
+    let kind = match token.token_t {
+        // Only a stream that doesn't check numbers leaves the value out.
+        Numeric => match token.value {
+            Some(number) => LiteralEpxr(Literal::Number(number)),
+            None => return Err(ParseError::new(vec![Numeric], token.into_owned())),
+        },
+        String => LiteralEpxr(Literal::Str(token.lexeme.into_owned())),
+        Ident => VariableExpr(Symbol::intern(&token.lexeme)),
+        True => BoolEpxr(true),
+        _ => BoolEpxr(false),
+    };
+    Ok(Expression::new(kind, token.span))
 }
 
-fn parse_primary(parser: &mut Parser) -> Expression {
-    match parser.next_token(1) {
-        Numeric | String => LiteralEpxr(parser.token(1)),
-        Ident => VariableExpr(parser.token(1)),
-        _ => panic!("Expected variable or literal found {:?}", parser.token(1)),
+// lambda_expr : ["|" [Ident Comma ?]* "|" | "||"] expression;
+fn parse_lambda_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
+    // `||` is a lambda without parameters.
+    let open = parser.token(1);
+    let mut params = Vec::new();
+    if open.lexeme == "|" {
+        loop {
+            match parser.next_token(1) {
+                Ident => params.push(Symbol::intern(&parser.token(1).lexeme)),
+                Comma => parser.consume(),
+                Operator if parser.lexeme(1) == "|" => break parser.consume(),
+                _ => return Err(parser.error_op(&[Ident, Comma, Operator], "|")),
+            }
+        }
     }
+    let body = parse_expr(parser)?;
+
+    let span = open.span.to(body.span);
+    Ok(Expression::new(LambdaExpr(params, Box::new(body)), span))
 }
 
-fn parse_binary_expr(parser: &mut Parser) -> Expression {
-    let lhs = match parser.next_token(1) {
-        Ident | Operator => parse_unary_expr(parser),
-        _ => panic!("Expected expresion found {:?}", parser.token(1)),
-    };
+// Comma separated expressions up to and including `close`, which is returned with them.
+fn parse_list<'a>(
+    parser: &mut Parser<'a>,
+    close: TokenType,
+) -> Result<(Vec<Expression>, Token<'a>), ParseError> {
+    let mut exprs = Vec::new();
+    loop {
+        if parser.next_token(1) == close {
+            return Ok((exprs, parser.token(1)));
+        }
+        exprs.push(parse_expr(parser)?);
+        match parser.next_token(1) {
+            Comma => parser.consume(),
+            token_t if token_t == close => {}
+            _ => return Err(parser.error(&[Comma, close])),
+        }
+    }
+}
 
-    let op = match parser.next_token(1) {
-        Operator => parser.token(1),
-        _ => return lhs,
-    };
+// if_expr : If expression Then expression Else expression;
+fn parse_if_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let keyword = parser.token(1);
+    let cond = parse_expr(parser)?;
+    parser.expect(Then)?;
+    let then = parse_expr(parser)?;
+    parser.expect(Else)?;
+    let otherwise = parse_expr(parser)?;
 
-    let rhs = match parser.next_token(1) {
-        Ident => parse_unary_expr(parser),
-        _ => panic!("Expected Ident found {:?}", parser.token(1)),
+    let span = keyword.span.to(otherwise.span);
+    Ok(Expression::new(
+        IfExpr(Box::new(cond), Box::new(then), Box::new(otherwise)),
+        span,
+    ))
+}
+
+// for_expr : For Ident "=" expression Comma expression [Comma expression]? In expression;
+fn parse_for_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let keyword = parser.token(1);
+    let var = Symbol::intern(&parser.expect(Ident)?.lexeme);
+    parser.expect_op("=")?;
+    let start = parse_expr(parser)?;
+    parser.expect(Comma)?;
+    let cond = parse_expr(parser)?;
+    let step = match parser.next_token(1) {
+        Comma => {
+            parser.consume();
+            Some(Box::new(parse_expr(parser)?))
+        }
+        In => None,
+        _ => return Err(parser.error(&[Comma, In])),
     };
+    parser.expect(In)?;
+    let body = parse_expr(parser)?;
 
-    BinaryExpr(op.lexeme, Box::new(lhs), Box::new(rhs))
+    let span = keyword.span.to(body.span);
+    Ok(Expression::new(
+        ForExpr(var, Box::new(start), Box::new(cond), step, Box::new(body)),
+        span,
+    ))
 }
 
-fn parse_extern(parser: &mut Parser) -> AST {
-    parser.consume();
-    let proto = parse_prototype(parser);
+// var_expr : Var Ident ["=" expression]? [Comma Ident ["=" expression]?]* In expression;
+fn parse_var_expr(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let keyword = parser.token(1);
+    let mut vars = Vec::new();
+    loop {
+        let name = Symbol::intern(&parser.expect(Ident)?.lexeme);
+        let init = match parser.next_token(1) {
+            Operator if parser.lexeme(1) == "=" => {
+                parser.consume();
+                Some(parse_expr(parser)?)
+            }
+            _ => None,
+        };
+        vars.push((name, init));
+        match parser.next_token(1) {
+            Comma => parser.consume(),
+            In => break parser.consume(),
+            _ => return Err(parser.error(&[Comma, In])),
+        }
+    }
+    let body = parse_expr(parser)?;
 
-    ExternNode(proto)
+    let span = keyword.span.to(body.span);
+    Ok(Expression::new(VarExpr(vars, Box::new(body)), span))
 }
 
-fn parse_prototype(parser: &mut Parser) -> ProtoType {
-    let name = match parser.next_token(1) {
-        Ident => parser.token(1),
-        _ => panic!("Expected function name found : {:?}", parser.token(1)),
+// block_expr : OpeningBrace [expression? Delimiter]* expression? ClosingBrace;
+fn parse_block(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let open = parser.token(1);
+    let mut exprs = Vec::new();
+    let mut value = None;
+    let close = loop {
+        match parser.next_token(1) {
+            RBrace => break parser.token(1),
+            Delimiter => parser.consume(),
+            _ => {
+                let expr = parse_expr(parser)?;
+                match parser.next_token(1) {
+                    Delimiter => exprs.push(expr),
+                    RBrace => value = Some(Box::new(expr)),
+                    _ => return Err(parser.error(&[Delimiter, RBrace])),
+                }
+            }
+        }
     };
 
-    match parser.next_token(1) {
-        LParenthesis => parser.consume(),
-        _ => panic!("Expected Open LParenthesis found : {:?}", parser.token(1)),
+    let span = open.span.to(close.span);
+    Ok(Expression::new(Block(exprs, value), span))
+}
+
+// Precedence climbing: keep folding operators that bind tighter than `min_prec` into `lhs`.
+fn parse_binary_expr(parser: &mut Parser, min_prec: u8) -> Result<Expression, ParseError> {
+    let mut lhs = parse_unary_expr(parser)?;
+
+    loop {
+        let (prec, assoc) = match parser.next_token(1) {
+            Operator => match binary_op(parser.lexeme(1)) {
+                Some((prec, assoc)) if prec > min_prec => (prec, assoc),
+                _ => return Ok(lhs),
+            },
+            _ => return Ok(lhs),
+        };
+        let op = parser.token(1);
+        let rhs = match assoc {
+            Assoc::Left => parse_binary_expr(parser, prec)?,
+            Assoc::Right => parse_binary_expr(parser, prec - 1)?,
+        };
+        let span = lhs.span.to(rhs.span);
+        let kind = match &*op.lexeme {
+            "=" => AssignExpr(Box::new(lhs), Box::new(rhs)),
+            _ => BinaryExpr(op.lexeme.into_owned(), Box::new(lhs), Box::new(rhs)),
+        };
+        lhs = Expression::new(kind, span);
     }
+}
+
+fn parse_extern(parser: &mut Parser) -> Result<AST, ParseError> {
+    let doc = parser.take_doc();
+    let keyword = parser.token(1);
+    let mut proto = parse_prototype(parser)?;
+    proto.doc = doc;
+
+    let span = keyword.span.to(proto.span);
+    Ok(ExternNode(proto, span))
+}
+
+// prototype : Ident OpeningParenthesis [param Comma ?]* ClosingParenthesis [Arrow type]?;
+// param     : Ident [Colon type]?;
+fn parse_prototype(parser: &mut Parser) -> Result<ProtoType, ParseError> {
+    let name = parser.expect(Ident)?;
+    parser.expect(LParenthesis)?;
 
     let mut args = Vec::new();
-    loop {
+    let mut arg_types = Vec::new();
+    let close = loop {
         match parser.next_token(1) {
-            Ident => args.push(parser.token(1)),
+            Ident => {
+                args.push(Symbol::intern(&parser.token(1).lexeme));
+                arg_types.push(match parser.next_token(1) {
+                    Colon => {
+                        parser.consume();
+                        Some(parse_type(parser)?)
+                    }
+                    _ => None,
+                });
+            }
             Comma => parser.consume(),
-            RParenthesis => break,
-            _ => panic!(
-                "Found {:?} when parsing args for {:?}",
-                parser.token(1),
-                name
-            ),
+            RParenthesis => break parser.token(1),
+            _ => return Err(parser.error(&[Ident, Comma, RParenthesis])),
         }
-    }
+    };
+    let mut span = name.span.to(close.span);
+    let ret_type = match parser.next_token(1) {
+        Arrow => {
+            parser.consume();
+            let ret_type = parse_type(parser)?;
+            span = span.to(ret_type.span());
+            Some(ret_type)
+        }
+        _ => None,
+    };
+
+    let mut proto = ProtoType::new(Symbol::intern(&name.lexeme), args, span);
+    proto.arg_types = arg_types;
+    proto.ret_type = ret_type;
+    Ok(proto)
+}
 
-    ProtoType::new(name, args)
+// type : Ident | OpeningBracket type ClosingBracket;
+fn parse_type(parser: &mut Parser) -> Result<Type, ParseError> {
+    match parser.next_token(1) {
+        Ident => {
+            let name = parser.token(1);
+            Ok(Type::Named(Symbol::intern(&name.lexeme), name.span))
+        }
+        LBracket => {
+            let open = parser.token(1);
+            let element = parse_type(parser)?;
+            let close = parser.expect(RBracket)?;
+            Ok(Type::Array(Box::new(element), open.span.to(close.span)))
+        }
+        _ => Err(parser.error(&[Ident, LBracket])),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lexer::{KBuff, KStream, Number, Span};
+    use ast::ExprKind;
+
+    // Expressions built by hand have no span, see `unspanned`.
+    fn node(kind: ExprKind) -> Expression {
+        Expression::new(kind, Span::default())
+    }
+
+    fn var(name: &str) -> Expression {
+        node(VariableExpr(Symbol::intern(name)))
+    }
+
+    fn int(n: i64) -> Expression {
+        node(LiteralEpxr(Literal::Number(Number::Int(n))))
+    }
+
+    fn boolean(b: bool) -> Expression {
+        node(BoolEpxr(b))
+    }
+
+    fn unary(op: &str, operand: Expression) -> Expression {
+        node(UnaryExpr(op.to_owned(), Box::new(operand)))
+    }
+
+    fn binary(op: &str, lhs: Expression, rhs: Expression) -> Expression {
+        node(BinaryExpr(op.to_owned(), Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn call(name: &str, args: Vec<Expression>) -> Expression {
+        node(CallExpr(Box::new(var(name)), args))
+    }
+
+    // Drop the spans from a parsed expression, to compare it against one built by hand.
+    fn unspanned(expr: Expression) -> Expression {
+        let kind = match expr.kind {
+            UnaryExpr(op, operand) => UnaryExpr(op, Box::new(unspanned(*operand))),
+            BinaryExpr(op, lhs, rhs) => {
+                BinaryExpr(op, Box::new(unspanned(*lhs)), Box::new(unspanned(*rhs)))
+            }
+            CallExpr(callee, args) => CallExpr(
+                Box::new(unspanned(*callee)),
+                args.into_iter().map(unspanned).collect(),
+            ),
+            LambdaExpr(params, body) => LambdaExpr(params, Box::new(unspanned(*body))),
+            IfExpr(cond, then, otherwise) => IfExpr(
+                Box::new(unspanned(*cond)),
+                Box::new(unspanned(*then)),
+                Box::new(unspanned(*otherwise)),
+            ),
+            ForExpr(var, start, cond, step, body) => ForExpr(
+                var,
+                Box::new(unspanned(*start)),
+                Box::new(unspanned(*cond)),
+                step.map(|step| Box::new(unspanned(*step))),
+                Box::new(unspanned(*body)),
+            ),
+            VarExpr(vars, body) => VarExpr(
+                vars.into_iter()
+                    .map(|(name, init)| (name, init.map(unspanned)))
+                    .collect(),
+                Box::new(unspanned(*body)),
+            ),
+            AssignExpr(target, value) => {
+                AssignExpr(Box::new(unspanned(*target)), Box::new(unspanned(*value)))
+            }
+            Block(exprs, value) => Block(
+                exprs.into_iter().map(unspanned).collect(),
+                value.map(|value| Box::new(unspanned(*value))),
+            ),
+            ArrayExpr(elements) => ArrayExpr(elements.into_iter().map(unspanned).collect()),
+            IndexExpr(array, index) => {
+                IndexExpr(Box::new(unspanned(*array)), Box::new(unspanned(*index)))
+            }
+            LenExpr(array) => LenExpr(Box::new(unspanned(*array))),
+            kind => kind,
+        };
+        node(kind)
+    }
+
+    fn expr(input: &str) -> Expression {
+        let mut parser = Parser::new(KBuff::new(input));
+        unspanned(parse_expr(&mut parser).unwrap())
+    }
 
     #[test]
     fn test_parse_prototype_with_args() {
         // Prototype  : Ident OpeningParenthesis [Ident Comma ?]* ClosingParenthesis;
         let lexer = KBuff::new("foo(x, y)");
-        let mut parser = Parser::new(4, lexer);
-        parser.fill_look_ahead();
+        let mut parser = Parser::new(lexer);
         let x = ProtoType::new(
-            Token::new(Ident, "foo".to_owned(), 0),
-            vec![
-                Token::new(Ident, "x".to_owned(), 0),
-                Token::new(Ident, "y".to_owned(), 0),
-            ],
+            Symbol::intern("foo"),
+            vec![Symbol::intern("x"), Symbol::intern("y")],
+            Span::new(0, 9, 1, 1),
         );
 
-        assert_eq!(x, parse_prototype(&mut parser));
+        assert_eq!(Ok(x), parse_prototype(&mut parser));
     }
 
     #[test]
     fn test_parse_prototype_no_args() {
         let lexer = KBuff::new("foo()");
-        let mut parser = Parser::new(4, lexer);
-        parser.fill_look_ahead();
-        let x = ProtoType::new(Token::new(Ident, "foo".to_owned(), 0), vec![]);
+        let mut parser = Parser::new(lexer);
+        let x = ProtoType::new(Symbol::intern("foo"), vec![], Span::new(0, 5, 1, 1));
 
-        assert_eq!(x, parse_prototype(&mut parser));
+        assert_eq!(Ok(x), parse_prototype(&mut parser));
+    }
+
+    #[test]
+    fn test_parse_typed_prototype() {
+        let named = |name, start, col| {
+            Some(Type::Named(
+                Symbol::intern(name),
+                Span::new(start, start + name.len(), 1, col),
+            ))
+        };
+
+        let mut parser = Parser::new(KBuff::new("def foo(x: int, y, z: [float]) -> bool x"));
+        let ast = parse(&mut parser);
+        let [FunctionNode(function)] = &ast[..] else {
+            panic!("Expected a def found {:?}", ast)
+        };
+        let proto = &function.prototype;
+        assert_eq!(proto.args.len(), 3);
+        assert_eq!(
+            proto.arg_types,
+            vec![
+                named("int", 11, 12),
+                None,
+                Some(Type::Array(
+                    Box::new(named("float", 23, 24).unwrap()),
+                    Span::new(22, 29, 1, 23)
+                )),
+            ]
+        );
+        assert_eq!(proto.ret_type, named("bool", 34, 35));
+        assert_eq!(proto.span, Span::new(4, 38, 1, 5));
+
+        let mut parser = Parser::new(KBuff::new("extern puts(s: str) -> int"));
+        let ast = parse(&mut parser);
+        let [ExternNode(puts, _)] = &ast[..] else {
+            panic!("Expected an extern found {:?}", ast)
+        };
+        assert_eq!(puts.arg_types, vec![named("str", 15, 16)]);
+        assert_eq!(puts.ret_type, named("int", 23, 24));
+        assert_eq!(ast[0].span(), Span::new(0, 26, 1, 1));
+
+        let mut parser = Parser::new(KBuff::new("foo(x:) -> 1"));
+        let err = parse_prototype(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Ident, LBracket]);
+        assert_eq!(err.found.token_t, RParenthesis);
     }
 
     #[test]
     fn test_parse_extern() {
         //declaration : Extern prototype;
         let lexer = KBuff::new("extern foo(x, y)");
-        let mut parser = Parser::new(4, lexer);
-        parser.fill_look_ahead();
-        let x = ExternNode(ProtoType::new(
-            Token::new(Ident, "foo".to_owned(), 0),
+        let mut parser = Parser::new(lexer);
+        let x = ExternNode(
+            ProtoType::new(
+                Symbol::intern("foo"),
+                vec![Symbol::intern("x"), Symbol::intern("y")],
+                Span::new(7, 16, 1, 8),
+            ),
+            Span::new(0, 16, 1, 1),
+        );
+
+        assert_eq!(Ok(x), parse_extern(&mut parser));
+    }
+
+    #[test]
+    fn test_parse_skips_lex_errors() {
+        let lexer = KBuff::new("extern foo($x, y)");
+        let mut parser = Parser::new(lexer);
+        let ast = parse(&mut parser);
+
+        assert_eq!(ast.len(), 1);
+        assert_eq!(
+            parser.lex_errors(),
+            &[LexError::UnexpectedChar('$', Span::new(11, 12, 1, 12))]
+        );
+    }
+
+    #[test]
+    fn test_parse_extern_doc_comment() {
+        let lexer = KBuff::new(
+            "/// Prints a line.\n/// Returns 0.\nextern puts(s) // trailing\n\nextern exit(c)\n/// Not kept.\n1",
+        );
+        let mut parser = Parser::new(lexer);
+        let ast = parse(&mut parser);
+
+        match &ast[..] {
+            [ExternNode(puts, _), ExternNode(exit, _), Expr(_)] => {
+                assert_eq!(puts.doc, Some("Prints a line.\nReturns 0.".to_owned()));
+                assert_eq!(exit.doc, None);
+            }
+            _ => panic!("Expected two externs and an expression found {:?}", ast),
+        }
+        // Docs are handed over to their item, and ones before anything else are dropped.
+        assert!(parser.docs.is_empty());
+    }
+
+    #[test]
+    fn test_parse_bool_expr() {
+        assert_eq!(expr("true"), boolean(true));
+        assert_eq!(expr("!false"), unary("!", boolean(false)));
+        assert_eq!(expr("x != true"), binary("!=", var("x"), boolean(true)));
+    }
+
+    #[test]
+    fn test_parse_from_stream() {
+        let lexer = KStream::new("extern foo(x, y)\nextern bar()".as_bytes());
+        let mut parser = Parser::new(lexer);
+        assert_eq!(
+            parse(&mut parser),
             vec![
-                Token::new(Ident, "x".to_owned(), 0),
-                Token::new(Ident, "y".to_owned(), 0),
-            ],
-        ));
+                ExternNode(
+                    ProtoType::new(
+                        Symbol::intern("foo"),
+                        vec![Symbol::intern("x"), Symbol::intern("y")],
+                        Span::new(7, 16, 1, 8),
+                    ),
+                    Span::new(0, 16, 1, 1),
+                ),
+                ExternNode(
+                    ProtoType::new(Symbol::intern("bar"), vec![], Span::new(24, 29, 2, 8)),
+                    Span::new(17, 29, 2, 1),
+                ),
+            ]
+        );
+    }
 
-        assert_eq!(x, parse_extern(&mut parser));
+    #[test]
+    fn test_parse_unchecked_number() {
+        // A stream other than the lexer may hand over a number without its value.
+        struct Unchecked(Vec<Token<'static>>);
+        impl<'a> TokenStream<'a> for Unchecked {
+            fn next_token(&mut self) -> Result<Token<'a>, LexError> {
+                let eof = Token::new(EOF, "", Span::new(3, 3, 1, 4));
+                Ok(self.0.pop().unwrap_or(eof))
+            }
+
+            fn warnings(&self) -> &[LexWarning] {
+                &[]
+            }
+        }
+
+        let number = Token::new(Numeric, "1x", Span::new(0, 2, 1, 1));
+        let mut parser = Parser::new(Unchecked(vec![number.clone()]));
+        assert_eq!(parse(&mut parser), vec![]);
+        assert_eq!(parser.errors(), &[ParseError::new(vec![Numeric], number)]);
+    }
+
+    #[test]
+    fn test_parse_lex_warnings() {
+        let mut parser = Parser::new(KStream::new("def f(paypal) pаypal".as_bytes()));
+        parse(&mut parser);
+        assert_eq!(parser.lex_warnings().len(), 2);
+        assert_eq!(parser.lex_warnings()[0].span(), Span::new(14, 21, 1, 15));
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let lexer =
+            KBuff::new("/// Adds.\ndef add(x, y) x + y;\nextern puts(s)\n\n1 + x;;\n\"done\"");
+        let mut parser = Parser::new(lexer);
+        let (x, y) = (Symbol::intern("x"), Symbol::intern("y"));
+        let mut add = ProtoType::new(Symbol::intern("add"), vec![x, y], Span::new(14, 23, 2, 5));
+        add.doc = Some("Adds.".to_owned());
+
+        let ast = parse(&mut parser);
+        match &ast[..] {
+            [FunctionNode(function), ExternNode(puts, extern_span), Expr(sum), Expr(done)] => {
+                assert_eq!(function.prototype, add);
+                assert_eq!(
+                    unspanned(function.body.clone()),
+                    binary("+", var("x"), var("y"))
+                );
+                assert_eq!(
+                    puts,
+                    &ProtoType::new(
+                        Symbol::intern("puts"),
+                        vec![Symbol::intern("s")],
+                        Span::new(38, 45, 3, 8)
+                    )
+                );
+                assert_eq!(*extern_span, Span::new(31, 45, 3, 1));
+                assert_eq!(unspanned(sum.clone()), binary("+", int(1), var("x")));
+                assert_eq!(
+                    unspanned(done.clone()),
+                    node(LiteralEpxr(Literal::Str("done".to_owned())))
+                );
+            }
+            _ => panic!("Expected def, extern and two expressions found {:?}", ast),
+        }
+        let spans: Vec<_> = ast.iter().map(AST::span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(10, 29, 2, 1),
+                Span::new(31, 45, 3, 1),
+                Span::new(47, 52, 5, 1),
+                Span::new(55, 61, 6, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        // a + b * c - 1 => (a + (b * c)) - 1
+        assert_eq!(
+            expr("a + b * c - 1"),
+            binary(
+                "-",
+                binary("+", var("a"), binary("*", var("b"), var("c"))),
+                int(1)
+            )
+        );
+        // 1 < 2 + -x * 3 => 1 < (2 + ((-x) * 3))
+        assert_eq!(
+            expr("1 < 2 + -x * 3"),
+            binary(
+                "<",
+                int(1),
+                binary("+", int(2), binary("*", unary("-", var("x")), int(3)))
+            )
+        );
+        assert_eq!(
+            expr("(a - b) * (2 - c) / 4"),
+            binary(
+                "/",
+                binary(
+                    "*",
+                    binary("-", var("a"), var("b")),
+                    binary("-", int(2), var("c"))
+                ),
+                int(4)
+            )
+        );
+        assert_eq!(
+            expr("a || b && !c == d"),
+            binary(
+                "||",
+                var("a"),
+                binary("&&", var("b"), binary("==", unary("!", var("c")), var("d")))
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_call_expr() {
+        assert_eq!(
+            expr("foo(a, b + 1, bar(2))"),
+            call(
+                "foo",
+                vec![
+                    var("a"),
+                    binary("+", var("b"), int(1)),
+                    call("bar", vec![int(2)])
+                ]
+            )
+        );
+        assert_eq!(expr("foo()"), call("foo", vec![]));
+        assert_eq!(
+            expr("foo(a, b,) * -bar()"),
+            binary(
+                "*",
+                call("foo", vec![var("a"), var("b")]),
+                unary("-", call("bar", vec![]))
+            )
+        );
+        assert_eq!(
+            expr("(a + b) * c"),
+            binary("*", binary("+", var("a"), var("b")), var("c"))
+        );
+    }
+
+    fn if_expr(cond: Expression, then: Expression, otherwise: Expression) -> Expression {
+        node(IfExpr(Box::new(cond), Box::new(then), Box::new(otherwise)))
+    }
+
+    #[test]
+    fn test_parse_if_expr() {
+        assert_eq!(
+            expr("if x < 1 then 1 else fib(x - 1) + 2"),
+            if_expr(
+                binary("<", var("x"), int(1)),
+                int(1),
+                binary(
+                    "+",
+                    call("fib", vec![binary("-", var("x"), int(1))]),
+                    int(2)
+                )
+            )
+        );
+        assert_eq!(
+            expr("if a then 1 else if b then 2 else 3"),
+            if_expr(var("a"), int(1), if_expr(var("b"), int(2), int(3)))
+        );
+        assert_eq!(
+            expr("2 * if a then b else c"),
+            binary("*", int(2), if_expr(var("a"), var("b"), var("c")))
+        );
+
+        let mut parser = Parser::new(KBuff::new("if a then b"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Else]);
+        assert_eq!(err.found.token_t, EOF);
+    }
+
+    #[test]
+    fn test_parse_for_expr() {
+        let i = Symbol::intern("i");
+        assert_eq!(
+            expr("for i = 1, i < n, 1.0 in putchard(42)"),
+            node(ForExpr(
+                i,
+                Box::new(int(1)),
+                Box::new(binary("<", var("i"), var("n"))),
+                Some(Box::new(node(LiteralEpxr(Literal::Number(Number::Float(
+                    1.0
+                )))))),
+                Box::new(call("putchard", vec![int(42)]))
+            ))
+        );
+        assert_eq!(
+            expr("for i = 0, i < 10 in for j = i, j < 10 in f(i, j)"),
+            node(ForExpr(
+                i,
+                Box::new(int(0)),
+                Box::new(binary("<", var("i"), int(10))),
+                None,
+                Box::new(node(ForExpr(
+                    Symbol::intern("j"),
+                    Box::new(var("i")),
+                    Box::new(binary("<", var("j"), int(10))),
+                    None,
+                    Box::new(call("f", vec![var("i"), var("j")]))
+                )))
+            ))
+        );
+
+        let mut parser = Parser::new(KBuff::new("for i = 0, i < 10 do"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Comma, In]);
+        assert_eq!(err.found.lexeme, "do");
+
+        let mut parser = Parser::new(KBuff::new("for i 1, i < n in x"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.to_string(), "1:7: expected \"=\" found \"1\"");
+    }
+
+    fn assign(target: Expression, value: Expression) -> Expression {
+        node(AssignExpr(Box::new(target), Box::new(value)))
+    }
+
+    #[test]
+    fn test_parse_var_expr() {
+        let (x, y) = (Symbol::intern("x"), Symbol::intern("y"));
+        assert_eq!(
+            expr("var x = 1, y in y = x = x + 1"),
+            node(VarExpr(
+                vec![(x, Some(int(1))), (y, None)],
+                Box::new(assign(
+                    var("y"),
+                    assign(var("x"), binary("+", var("x"), int(1)))
+                ))
+            ))
+        );
+        assert_eq!(
+            expr("a == b = c || d"),
+            assign(
+                binary("==", var("a"), var("b")),
+                binary("||", var("c"), var("d"))
+            )
+        );
+
+        let mut parser = Parser::new(KBuff::new("var x = 1 x"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Comma, In]);
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let (a, b) = (var("a"), var("b"));
+        assert_eq!(
+            expr("{ f(a); a = b; b }"),
+            node(Block(
+                vec![call("f", vec![a.clone()]), assign(a.clone(), b.clone())],
+                Some(Box::new(b.clone()))
+            ))
+        );
+        assert_eq!(
+            expr("{ a;; b; }"),
+            node(Block(vec![a.clone(), b.clone()], None))
+        );
+        assert_eq!(expr("{}"), node(Block(vec![], None)));
+        assert_eq!(
+            expr("1 + { a }"),
+            binary("+", int(1), node(Block(vec![], Some(Box::new(a)))))
+        );
+
+        let mut parser = Parser::new(KBuff::new("def f(x) {\n  x;\n  x + 1\n}"));
+        let ast = parse(&mut parser);
+        assert_eq!(ast[0].span(), Span::new(0, 25, 1, 1));
+
+        let mut parser = Parser::new(KBuff::new("{ a b }"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Delimiter, RBrace]);
+    }
+
+    fn array(elements: Vec<Expression>) -> Expression {
+        node(ArrayExpr(elements))
+    }
+
+    fn index(array: Expression, index: Expression) -> Expression {
+        node(IndexExpr(Box::new(array), Box::new(index)))
+    }
+
+    #[test]
+    fn test_parse_arrays() {
+        assert_eq!(
+            expr("[1, [], [a,],]"),
+            array(vec![int(1), array(vec![]), array(vec![var("a")])])
+        );
+        assert_eq!(
+            expr("-a[i + 1][0] * 2"),
+            binary(
+                "*",
+                unary(
+                    "-",
+                    index(index(var("a"), binary("+", var("i"), int(1))), int(0))
+                ),
+                int(2)
+            )
+        );
+        assert_eq!(
+            expr("a[len(a) - 1] = f(x)[0]"),
+            assign(
+                index(var("a"), binary("-", call("len", vec![var("a")]), int(1))),
+                index(call("f", vec![var("x")]), int(0))
+            )
+        );
+        assert_eq!(expr("len(a, b)"), call("len", vec![var("a"), var("b")]));
+
+        let mut parser = Parser::new(KBuff::new("a[1 2]"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![RBracket]);
+        let mut parser = Parser::new(KBuff::new("[1 2]"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Comma, RBracket]);
+    }
+
+    #[test]
+    fn test_parse_method_call() {
+        let float = node(LiteralEpxr(Literal::Number(Number::Float(1.5))));
+        assert_eq!(expr("144.sqrt()"), call("sqrt", vec![int(144)]));
+        assert_eq!(
+            expr("-x.pow(2, ).abs() + 1.5.floor()"),
+            binary(
+                "+",
+                unary("-", call("abs", vec![call("pow", vec![var("x"), int(2)])])),
+                call("floor", vec![float])
+            )
+        );
+        assert_eq!(
+            expr("a[0].len()"),
+            call("len", vec![index(var("a"), int(0))])
+        );
+
+        let mut parser = Parser::new(KBuff::new("x.y + 1"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![LParenthesis]);
+
+        let mut parser = Parser::new(KBuff::new("f(x).g(y)"));
+        assert_eq!(parse_expr(&mut parser).unwrap().span, Span::new(0, 9, 1, 1));
+    }
+
+    fn lambda(params: &[&str], body: Expression) -> Expression {
+        let params = params.iter().map(|param| Symbol::intern(param)).collect();
+        node(LambdaExpr(params, Box::new(body)))
+    }
+
+    #[test]
+    fn test_parse_lambda() {
+        assert_eq!(
+            expr("|x, y| x + y"),
+            lambda(&["x", "y"], binary("+", var("x"), var("y")))
+        );
+        assert_eq!(
+            expr("fold(a, 0, |acc, x,| acc + x * k)"),
+            call(
+                "fold",
+                vec![
+                    var("a"),
+                    int(0),
+                    lambda(
+                        &["acc", "x"],
+                        binary("+", var("acc"), binary("*", var("x"), var("k")))
+                    )
+                ]
+            )
+        );
+        assert_eq!(expr("|| 1"), lambda(&[], int(1)));
+        assert_eq!(
+            expr("(|x| |y| x)(1)(2) || b"),
+            binary(
+                "||",
+                node(CallExpr(
+                    Box::new(node(CallExpr(
+                        Box::new(lambda(&["x"], lambda(&["y"], var("x")))),
+                        vec![int(1)]
+                    ))),
+                    vec![int(2)]
+                )),
+                var("b")
+            )
+        );
+        assert_eq!(
+            expr("fs[0](x)"),
+            node(CallExpr(Box::new(index(var("fs"), int(0))), vec![var("x")]))
+        );
+
+        let mut parser = Parser::new(KBuff::new("|x, 1| x"));
+        let err = parse_expr(&mut parser).unwrap_err();
+        assert_eq!(err.expected, vec![Ident, Comma, Operator]);
+        assert_eq!(
+            err.to_string(),
+            "1:5: expected one of Ident, Comma, \"|\" found \"1\""
+        );
+
+        let mut parser = Parser::new(KBuff::new("f(|x| x)"));
+        let expr = parse_expr(&mut parser).unwrap();
+        let CallExpr(callee, args) = &expr.kind else {
+            panic!("Expected a call found {:?}", expr)
+        };
+        assert_eq!(callee.span, Span::new(0, 1, 1, 1));
+        assert_eq!(args[0].span, Span::new(2, 7, 1, 3));
+    }
+
+    #[test]
+    fn test_parse_spans() {
+        let mut parser = Parser::new(KBuff::new("foo(a, (b + 1)) * -c"));
+        let expr = parse_expr(&mut parser).unwrap();
+        assert_eq!(expr.span, Span::new(0, 20, 1, 1));
+        let BinaryExpr(_, lhs, rhs) = &expr.kind else {
+            panic!("Expected a binary expression found {:?}", expr)
+        };
+        assert_eq!(lhs.span, Span::new(0, 15, 1, 1));
+        assert_eq!(rhs.span, Span::new(18, 20, 1, 19));
+        let CallExpr(_, args) = &lhs.kind else {
+            panic!("Expected a call found {:?}", lhs)
+        };
+        assert_eq!(args[0].span, Span::new(4, 5, 1, 5));
+        assert_eq!(args[1].span, Span::new(7, 14, 1, 8));
+
+        let mut parser = Parser::new(KBuff::new("def f(x)\n  x +\n  1"));
+        let ast = parse(&mut parser);
+        let [FunctionNode(function)] = &ast[..] else {
+            panic!("Expected a def found {:?}", ast)
+        };
+        assert_eq!(function.span, Span::new(0, 18, 1, 1));
+        assert_eq!(function.prototype.span, Span::new(4, 8, 1, 5));
+        assert_eq!(function.body.span, Span::new(11, 18, 2, 3));
+
+        let mut parser = Parser::new(KBuff::new("x + a[i][j]"));
+        let expr = parse_expr(&mut parser).unwrap();
+        let BinaryExpr(_, _, rhs) = &expr.kind else {
+            panic!("Expected a binary expression found {:?}", expr)
+        };
+        assert_eq!(rhs.span, Span::new(4, 11, 1, 5));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let lexer = KBuff::new("def foo(x, 1) x;\nextern (y);\n1 + * 2;\ndef ok() 1\n2 )");
+        let mut parser = Parser::new(lexer);
+        let ast = parse(&mut parser);
+
+        match &ast[..] {
+            [FunctionNode(ok), Expr(two)] => {
+                assert_eq!(ok.prototype.func_name, Symbol::intern("ok"));
+                assert_eq!(unspanned(ok.body.clone()), int(1));
+                assert_eq!(unspanned(two.clone()), int(2));
+            }
+            _ => panic!("Expected a def and an expression found {:?}", ast),
+        }
+
+        let found = |err: &ParseError| (err.found.token_t, err.found.lexeme.to_string());
+        let errors = parser.errors();
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0].expected, vec![Ident, Comma, RParenthesis]);
+        assert_eq!(found(&errors[0]), (Numeric, "1".to_owned()));
+        assert_eq!(errors[0].span(), Span::new(11, 12, 1, 12));
+        assert_eq!(errors[1].expected, vec![Ident]);
+        assert_eq!(found(&errors[1]), (LParenthesis, "".to_owned()));
+        assert_eq!(errors[2].expected, EXPR_START);
+        assert_eq!(found(&errors[2]), (Operator, "*".to_owned()));
+        assert_eq!(found(&errors[3]), (RParenthesis, "".to_owned()));
+        assert_eq!(
+            errors[1].to_string(),
+            "2:8: expected Ident found LParenthesis"
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "1:12: expected one of Ident, Comma, RParenthesis found \"1\""
+        );
+        // The full set of tokens an expression can start with is checked above.
+        let message = errors[2].to_string();
+        assert!(message.starts_with("3:5: expected one of "), "{}", message);
+        assert!(message.ends_with(" found \"*\""), "{}", message);
+    }
+
+    #[test]
+    fn test_parse_error_in_block() {
+        // The `;` inside the block doesn't end the broken item.
+        let lexer = KBuff::new(
+            "def f(x) { 1 + ; 2 }  def g() 3
+{ [1, ; 2] }; 4",
+        );
+        let mut parser = Parser::new(lexer);
+        let ast = parse(&mut parser);
+
+        match &ast[..] {
+            [FunctionNode(g), Expr(four)] => {
+                assert_eq!(g.prototype.func_name, Symbol::intern("g"));
+                assert_eq!(unspanned(four.clone()), int(4));
+            }
+            _ => panic!("Expected a def and an expression found {:?}", ast),
+        }
+        let errors = parser.errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].span(), Span::new(15, 16, 1, 16));
+        assert_eq!(errors[1].span(), Span::new(38, 39, 2, 7));
+    }
+
+    #[test]
+    fn test_peek_past_look_ahead() {
+        let mut parser = Parser::new(KBuff::new("a b c d e f"));
+        assert_eq!(parser.peek(6).lexeme, "f");
+        assert_eq!(parser.peek(7).token_t, EOF);
+        assert_eq!(parser.peek(10).token_t, EOF);
+        assert_eq!(parser.token(1).lexeme, "a");
+        assert_eq!(parser.peek(5).lexeme, "f");
+    }
+
+    #[test]
+    #[should_panic(expected = "tokens are peeked from 1")]
+    fn test_peek_zero() {
+        Parser::new(KBuff::new("a")).peek(0);
+    }
+
+    #[test]
+    fn test_mark_rewind() {
+        let mut parser = Parser::new(KBuff::new("1 + foo(2, x) y"));
+
+        // A failed speculative parse gives back everything it consumed.
+        let mark = parser.mark();
+        assert!(parse_prototype(&mut parser).is_err());
+        parser.rewind(mark);
+        let outer = parser.mark();
+        let expr = parse_expr(&mut parser).unwrap();
+        assert_eq!(parser.lexeme(1), "y");
+
+        let inner = parser.mark();
+        parser.consume();
+        parser.release(inner);
+        assert_eq!(parser.next_token(1), EOF);
+        parser.rewind(outer);
+        assert_eq!(parse_expr(&mut parser), Ok(expr));
+
+        // Once no mark is left the consumed tokens are dropped.
+        let mark = parser.mark();
+        parser.consume();
+        parser.release(mark);
+        assert_eq!(parser.pos, 0);
+        assert_eq!(parser.look_ahead.len(), 1);
+        assert_eq!(parser.next_token(1), EOF);
+    }
+
+    #[test]
+    #[should_panic(expected = "marks released out of order")]
+    fn test_marks_release_in_order() {
+        let mut parser = Parser::new(KBuff::new("a b"));
+        let outer = parser.mark();
+        let inner = parser.mark();
+        parser.release(outer);
+        parser.release(inner);
     }
 }
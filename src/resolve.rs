@@ -0,0 +1,362 @@
+use crate::lexer::Span;
+use crate::parser::ast::{ExprKind::*, Expression, ProtoType, Type, AST, AST::*};
+use crate::symbol::Symbol;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ResolveError {
+    Undefined(Symbol, Span),
+    // Assigning to a parameter, loop variable or function, only `var` bindings are mutable.
+    Immutable(Symbol, Span),
+    InvalidAssignTarget(Span),
+    UnknownType(Symbol, Span),
+}
+
+impl ResolveError {
+    pub fn span(&self) -> Span {
+        match self {
+            ResolveError::Undefined(_, span)
+            | ResolveError::Immutable(_, span)
+            | ResolveError::InvalidAssignTarget(span)
+            | ResolveError::UnknownType(_, span) => *span,
+        }
+    }
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ResolveError::Undefined(name, span) => {
+                write!(f, "{}: cannot find `{}` in scope", span, name)
+            }
+            ResolveError::Immutable(name, span) => {
+                write!(
+                    f,
+                    "{}: cannot assign to `{}`, it is not a `var`",
+                    span, name
+                )
+            }
+            ResolveError::InvalidAssignTarget(span) => {
+                write!(f, "{}: cannot assign to this expression", span)
+            }
+            ResolveError::UnknownType(name, span) => {
+                write!(f, "{}: cannot find type `{}`", span, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+// Types an annotation can name.
+pub const TYPES: &[&str] = &["int", "float", "bool", "str"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Binding {
+    Param,
+    Loop,
+    Var,
+    Function,
+}
+
+#[derive(Default)]
+struct Resolver {
+    // Variables in scope, innermost last.
+    scope: Vec<(Symbol, Binding)>,
+    // Every `def` and `extern`, usable anywhere as values.
+    functions: HashSet<Symbol>,
+    errors: Vec<ResolveError>,
+}
+
+// Check every variable use and assignment against the bindings in scope.
+// Calls to `len` not bound in scope become the builtin.
+pub fn resolve(ast: &mut [AST]) -> Vec<ResolveError> {
+    let mut resolver = Resolver::default();
+    for item in ast.iter() {
+        match item {
+            ExternNode(proto, _) => resolver.functions.insert(proto.func_name),
+            FunctionNode(function) => resolver.functions.insert(function.prototype.func_name),
+            Expr(_) => false,
+        };
+    }
+    for item in ast {
+        match item {
+            ExternNode(proto, _) => resolver.prototype(proto),
+            FunctionNode(function) => {
+                resolver.prototype(&function.prototype);
+                for &arg in &function.prototype.args {
+                    resolver.scope.push((arg, Binding::Param));
+                }
+                resolver.expr(&mut function.body);
+                resolver.scope.clear();
+            }
+            Expr(expr) => resolver.expr(expr),
+        }
+    }
+    resolver.errors
+}
+
+impl Resolver {
+    fn prototype(&mut self, proto: &ProtoType) {
+        for ty in proto.arg_types.iter().chain([&proto.ret_type]).flatten() {
+            self.ty(ty);
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Named(name, span) => {
+                if !TYPES.contains(&name.as_str()) {
+                    self.errors.push(ResolveError::UnknownType(*name, *span));
+                }
+            }
+            Type::Array(element, _) => self.ty(element),
+        }
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<Binding> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(bound, _)| *bound == name)
+            .map(|&(_, binding)| binding)
+            .or_else(|| self.functions.contains(&name).then_some(Binding::Function))
+    }
+
+    // `len(a)` is the builtin unless a binding named `len` is in scope.
+    fn builtin(&self, expr: &mut Expression) {
+        if let CallExpr(callee, args) = &mut expr.kind {
+            if let VariableExpr(name) = callee.kind {
+                if name.as_str() == "len" && args.len() == 1 && self.lookup(name).is_none() {
+                    expr.kind = LenExpr(Box::new(args.remove(0)));
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expression) {
+        self.builtin(expr);
+        match &mut expr.kind {
+            LiteralEpxr(_) | BoolEpxr(_) => {}
+            VariableExpr(name) => {
+                if self.lookup(*name).is_none() {
+                    self.errors.push(ResolveError::Undefined(*name, expr.span));
+                }
+            }
+            BinaryExpr(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            UnaryExpr(_, operand) => self.expr(operand),
+            CallExpr(callee, args) => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            IfExpr(cond, then, otherwise) => {
+                self.expr(cond);
+                self.expr(then);
+                self.expr(otherwise);
+            }
+            ForExpr(var, start, cond, step, body) => {
+                self.expr(start);
+                self.scope.push((*var, Binding::Loop));
+                self.expr(cond);
+                if let Some(step) = step {
+                    self.expr(step);
+                }
+                self.expr(body);
+                self.scope.pop();
+            }
+            VarExpr(vars, body) => {
+                let depth = self.scope.len();
+                // Each initial value sees the bindings before it, but not its own.
+                for (name, init) in vars {
+                    if let Some(init) = init {
+                        self.expr(init);
+                    }
+                    self.scope.push((*name, Binding::Var));
+                }
+                self.expr(body);
+                self.scope.truncate(depth);
+            }
+            Block(exprs, value) => {
+                for expr in exprs {
+                    self.expr(expr);
+                }
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            ArrayExpr(elements) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            IndexExpr(array, index) => {
+                self.expr(array);
+                self.expr(index);
+            }
+            LenExpr(array) => self.expr(array),
+            // The body sees the variables around the lambda as well as its parameters.
+            LambdaExpr(params, body) => {
+                let depth = self.scope.len();
+                for &param in params.iter() {
+                    self.scope.push((param, Binding::Param));
+                }
+                self.expr(body);
+                self.scope.truncate(depth);
+            }
+            AssignExpr(target, value) => {
+                self.expr(value);
+                let err = match target.kind {
+                    VariableExpr(name) => match self.lookup(name) {
+                        Some(Binding::Var) => return,
+                        Some(_) => ResolveError::Immutable(name, target.span),
+                        None => ResolveError::Undefined(name, target.span),
+                    },
+                    // Setting an element changes the array, not the variable holding it.
+                    IndexExpr(..) => return self.expr(target),
+                    _ => ResolveError::InvalidAssignTarget(target.span),
+                };
+                self.errors.push(err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::KBuff;
+    use crate::parser::{parse, Parser};
+
+    fn resolve_str(input: &str) -> Vec<ResolveError> {
+        let mut parser = Parser::new(KBuff::new(input));
+        let mut ast = parse(&mut parser);
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        resolve(&mut ast)
+    }
+
+    #[test]
+    fn test_resolve_scopes() {
+        let input = "def f(x) var a = x, b = a in for i = a, i < b in a = b = i + x";
+        assert_eq!(resolve_str(input), vec![]);
+
+        let (a, x) = (Symbol::intern("a"), Symbol::intern("x"));
+        assert_eq!(
+            resolve_str("var a = a in a\ndef g(y) y + x"),
+            vec![
+                ResolveError::Undefined(a, Span::new(8, 9, 1, 9)),
+                ResolveError::Undefined(x, Span::new(28, 29, 2, 14)),
+            ]
+        );
+        // The loop variable is gone after the loop, and so are parameters after their def.
+        assert_eq!(
+            resolve_str("def h(x) (for i = 0, i < x in 1) + i; x"),
+            vec![
+                ResolveError::Undefined(Symbol::intern("i"), Span::new(35, 36, 1, 36)),
+                ResolveError::Undefined(x, Span::new(38, 39, 1, 39)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_assignment() {
+        let (x, i) = (Symbol::intern("x"), Symbol::intern("i"));
+        let errors = resolve_str("def f(x) x = 1\nfor i = 0, i < 3 in i = 2\nvar v in f(v) = 1");
+        assert_eq!(
+            errors,
+            vec![
+                ResolveError::Immutable(x, Span::new(9, 10, 1, 10)),
+                ResolveError::Immutable(i, Span::new(35, 36, 2, 21)),
+                ResolveError::InvalidAssignTarget(Span::new(50, 54, 3, 10)),
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "1:10: cannot assign to `x`, it is not a `var`"
+        );
+        assert_eq!(resolve_str("def g(a, i) a[i] = len(a)"), vec![]);
+        assert_eq!(
+            resolve_str("y = 1"),
+            vec![ResolveError::Undefined(
+                Symbol::intern("y"),
+                Span::new(0, 1, 1, 1)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_resolve_types() {
+        let errors =
+            resolve_str("extern puts(s: str) -> int\ndef f(x: [[float]], y: num) -> [bol] x");
+        assert_eq!(
+            errors,
+            vec![
+                ResolveError::UnknownType(Symbol::intern("num"), Span::new(50, 53, 2, 24)),
+                ResolveError::UnknownType(Symbol::intern("bol"), Span::new(59, 62, 2, 33)),
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "2:24: cannot find type `num`");
+    }
+
+    #[test]
+    fn test_resolve_functions_and_lambdas() {
+        let input = "extern sqrt(x)\n\
+            def fold(a, init, f) var acc = init in {\n\
+                for i = 0, i < len(a) in acc = f(acc, a[i]);\n\
+                acc\n\
+            }\n\
+            def sum(a) var k = 2 in fold(a, 0, |acc, x| acc + x * k)\n\
+            fold([1.0.sqrt()], 0, sum)";
+        assert_eq!(resolve_str(input), vec![]);
+
+        let (acc, sum) = (Symbol::intern("acc"), Symbol::intern("sum"));
+        assert_eq!(
+            resolve_str("def sum(a) (|x| x + acc)(a) + later(a)\nsum = |x| x\ndef later(b) b"),
+            vec![
+                ResolveError::Undefined(acc, Span::new(20, 23, 1, 21)),
+                ResolveError::Immutable(sum, Span::new(39, 42, 2, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_len_builtin() {
+        let bodies = |input| {
+            let mut ast = parse(&mut Parser::new(KBuff::new(input)));
+            assert_eq!(resolve(&mut ast), vec![]);
+            ast.into_iter()
+                .map(|item| match item {
+                    FunctionNode(function) => function.body.kind,
+                    Expr(expr) => expr.kind,
+                    ExternNode(..) => panic!("Expected no externs found {:?}", item),
+                })
+                .collect::<Vec<_>>()
+        };
+        // Unbound, one argument calls to `len` are the builtin, any binding shadows it.
+        match &bodies("def f(a) len(a) + a.len()\ndef g(len) len(1)\nvar len = |a| 0 in len([1])")[..]
+        {
+            [BinaryExpr(_, lhs, rhs), CallExpr(..), VarExpr(_, body)] => {
+                assert!(matches!(lhs.kind, LenExpr(_)), "{:?}", lhs);
+                assert!(matches!(rhs.kind, LenExpr(_)), "{:?}", rhs);
+                assert!(matches!(body.kind, CallExpr(..)), "{:?}", body);
+            }
+            kinds => panic!("Unexpected bodies {:?}", kinds),
+        }
+        match &bodies("def len(a) 0\nlen([1])")[..] {
+            [_, CallExpr(..)] => {}
+            kinds => panic!("Expected a call to `len` found {:?}", kinds),
+        }
+        assert_eq!(
+            resolve_str("len(1, 2)"),
+            vec![ResolveError::Undefined(
+                Symbol::intern("len"),
+                Span::new(0, 3, 1, 1)
+            )]
+        );
+    }
+}